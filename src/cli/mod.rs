@@ -0,0 +1,191 @@
+use std::{env, fs, path::{Path, PathBuf}, str::FromStr};
+
+pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+// overrides the database location like --db does
+pub const DB_ENV_VAR: &str = "LETTER_DB";
+// name of a project database, letter looks for it in the working directory and above
+pub const PROJECT_DB_NAME: &str = ".letter.db";
+
+// formats tasks can be exported to and imported from
+#[derive(Clone, Copy)]
+pub enum Format {
+    Markdown,
+    TodoTxt,
+    // VTODO components of an iCalendar file
+    Ical,
+    // what `task export` writes, it can only be imported
+    Taskwarrior,
+    // the whole database, see store::dump
+    Json
+}
+
+impl Format {
+    // guessed from the extension of the file
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "markdown" | "md" => Ok(Format::Markdown),
+            "todotxt" | "todo.txt" | "txt" => Ok(Format::TodoTxt),
+            "ical" | "ics" => Ok(Format::Ical),
+            "taskwarrior" | "tw" => Ok(Format::Taskwarrior),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("unknown format {s}"))
+        }
+    }
+}
+
+// how Taskwarrior tasks are ordered when they're imported, most urgent or oldest first
+#[derive(Clone, Copy, Default)]
+pub enum TaskOrder {
+    #[default]
+    Urgency,
+    Entry
+}
+
+impl FromStr for TaskOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "urgency" => Ok(TaskOrder::Urgency),
+            "entry" => Ok(TaskOrder::Entry),
+            _ => Err(format!("unknown order {s}, try urgency or entry"))
+        }
+    }
+}
+
+// what letter does instead of opening the editor
+pub enum Command {
+    // checks the database for broken references and order, `letter fsck [--dry-run]`
+    Fsck { dry_run: bool },
+    // `letter export [--format f] [--output path]`, without a path it goes to stdout
+    Export { format: Option<Format>, output: Option<PathBuf> },
+    // `letter import [--format f] [--replace] [--dry-run] [--order o] [path]`, without a path it
+    // comes from stdin, a dry run shows what a JSON dump would change
+    Import { format: Option<Format>, input: Option<PathBuf>, replace: bool, dry_run: bool, order: Option<TaskOrder> }
+}
+
+pub struct Args {
+    pub db: Option<PathBuf>,
+    pub in_memory: bool,
+    // name of the list to open instead of the first one
+    pub list: Option<String>,
+    pub command: Option<Command>
+}
+
+fn next_value(raw_args: &mut impl Iterator<Item = String>, flag: &str) -> Result<String> {
+    raw_args.next().ok_or_else(|| format!("{flag} needs a value").into())
+}
+
+impl Args {
+    pub fn parse() -> Result<Self> {
+        let mut args = Self {
+            db: None,
+            in_memory: false,
+            list: None,
+            command: None
+        };
+
+        let mut raw_args = env::args().skip(1);
+        while let Some(arg) = raw_args.next() {
+            match arg.as_str() {
+                "--db" => {
+                    let path = raw_args.next().ok_or_else(|| "--db needs a path".to_string())?;
+                    args.db = Some(PathBuf::from(path));
+                },
+                "--in-memory" => args.in_memory = true,
+                "--list" => args.list = Some(next_value(&mut raw_args, "--list")?),
+                "fsck" if args.command.is_none() => args.command = Some(Command::Fsck { dry_run: false }),
+                "export" if args.command.is_none() => args.command = Some(Command::Export { format: None, output: None }),
+                "import" if args.command.is_none() => args.command = Some(Command::Import { format: None, input: None, replace: false, dry_run: false, order: None }),
+                "--dry-run" => match &mut args.command {
+                    Some(Command::Fsck { dry_run } | Command::Import { dry_run, .. }) => *dry_run = true,
+                    _ => return Err("--dry-run only works with fsck and import".into())
+                },
+                "--format" => {
+                    let value: Format = next_value(&mut raw_args, "--format")?.parse()?;
+                    match &mut args.command {
+                        Some(Command::Export { format, .. } | Command::Import { format, .. }) => *format = Some(value),
+                        _ => return Err("--format only works with export and import".into())
+                    }
+                },
+                "--output" | "-o" => match &mut args.command {
+                    Some(Command::Export { output, .. }) => *output = Some(PathBuf::from(next_value(&mut raw_args, &arg)?)),
+                    _ => return Err(format!("{arg} only works with export").into())
+                },
+                "--order" => {
+                    let value: TaskOrder = next_value(&mut raw_args, "--order")?.parse()?;
+                    match &mut args.command {
+                        Some(Command::Import { order, .. }) => *order = Some(value),
+                        _ => return Err("--order only works with import".into())
+                    }
+                },
+                "--replace" => match &mut args.command {
+                    Some(Command::Import { replace, .. }) => *replace = true,
+                    _ => return Err("--replace only works with import".into())
+                },
+                _ if arg.starts_with("--db=") => args.db = Some(PathBuf::from(&arg["--db=".len()..])),
+                _ => match &mut args.command {
+                    // the file to import, `-` reads stdin
+                    Some(Command::Import { input: input @ None, .. }) if !arg.starts_with("--") => *input = Some(PathBuf::from(arg)),
+                    // a Markdown file can be given on its own
+                    _ if arg.ends_with(".md") => args.db = Some(PathBuf::from(arg)),
+                    _ => return Err(format!("unknown argument {arg}").into())
+                }
+            }
+        }
+
+        Ok(args)
+    }
+
+    // --db first, then the environment, then a project database and last the global one
+    pub fn database_path(&self) -> Result<PathBuf> {
+        if let Some(db) = &self.db {
+            return Ok(db.clone());
+        }
+
+        if let Some(db) = env::var_os(DB_ENV_VAR).filter(|db| !db.is_empty()) {
+            return Ok(PathBuf::from(db));
+        }
+
+        if let Some(db) = find_project_database(&env::current_dir()?) {
+            return Ok(db);
+        }
+
+        let data_dir = global_data_dir().ok_or_else(|| "neither XDG_DATA_HOME nor HOME is set".to_string())?;
+        fs::create_dir_all(&data_dir)?;
+        Ok(data_dir.join("letter.db"))
+    }
+}
+
+fn find_project_database(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(PROJECT_DB_NAME))
+        .find(|db| db.is_file())
+}
+
+fn global_data_dir() -> Option<PathBuf> {
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))?;
+
+    Some(data_home.join("letter"))
+}
+
+// shortens paths below the home directory to `~/…` for display
+pub fn display_path(path: &Path) -> String {
+    let home = env::var_os("HOME").map(PathBuf::from);
+    match home.and_then(|home| path.strip_prefix(home).ok().map(Path::to_path_buf)) {
+        Some(relative) => format!("~/{}", relative.display()),
+        None => path.display().to_string()
+    }
+}
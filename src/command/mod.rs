@@ -1,9 +1,11 @@
 use std::{collections::HashMap, sync::mpsc::{Sender, Receiver, self}};
 
-use crossterm::event::KeyCode;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
 pub struct KeyCommandComposer<C: Copy> {
     command_registry: HashMap<Vec<KeyCode>, C>,
+    // commands bound to a single key pressed together with ctrl
+    ctrl_command_registry: HashMap<KeyCode, C>,
     current_composition: Vec<KeyCode>,
     tx: Sender<C>,
 }
@@ -19,7 +21,7 @@ fn key_code_to_string(key_code: &KeyCode) -> String {
 impl<C: Copy> KeyCommandComposer<C> {
     pub fn new() -> (Self, Receiver<C>) {
         let (tx, rx) = mpsc::channel();
-        (KeyCommandComposer { command_registry: HashMap::new(), current_composition: Vec::new(), tx }, rx)
+        (KeyCommandComposer { command_registry: HashMap::new(), ctrl_command_registry: HashMap::new(), current_composition: Vec::new(), tx }, rx)
     }
 
     pub fn _len(&self) -> usize {
@@ -32,6 +34,18 @@ impl<C: Copy> KeyCommandComposer<C> {
             .collect()
     }
 
+    pub fn push_key_event(&mut self, key_event: KeyEvent) {
+        if !key_event.modifiers.contains(KeyModifiers::CONTROL) {
+            self.push_key(key_event.code);
+            return;
+        }
+
+        self.clear_composition();
+        if let Some(command) = self.ctrl_command_registry.get(&key_event.code) {
+            self.tx.send(*command).unwrap();
+        }
+    }
+
     pub fn push_key(&mut self, key_code: KeyCode) {
         let key_code = key_code.clone();
         if key_code == KeyCode::Esc {
@@ -71,5 +85,9 @@ impl<C: Copy> KeyCommandComposer<C> {
     pub fn register_keycommand(&mut self, key_chain: Vec<KeyCode>, cmd: C) {
         self.command_registry.insert(key_chain, cmd);
     }
+
+    pub fn register_ctrl_keycommand(&mut self, key_code: KeyCode, cmd: C) {
+        self.ctrl_command_registry.insert(key_code, cmd);
+    }
 }
 
@@ -0,0 +1,144 @@
+use chrono::{NaiveDate, NaiveDateTime, Duration, Local, Days, Months, Weekday, Datelike, Timelike, TimeZone, Utc};
+
+mod recurrence;
+
+pub use recurrence::Recurrence;
+
+// due dates this many days ahead count as upcoming
+const UPCOMING_DAYS: i64 = 7;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum DueStatus {
+    Overdue,
+    Today,
+    Upcoming,
+    Later
+}
+
+pub fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+// timestamps are local time to the second
+pub fn now() -> NaiveDateTime {
+    let now = Local::now().naive_local();
+    now.with_nanosecond(0).unwrap_or(now)
+}
+
+pub fn due_status(due_date: NaiveDate, today: NaiveDate) -> DueStatus {
+    match (due_date - today).num_days() {
+        days if days < 0 => DueStatus::Overdue,
+        0 => DueStatus::Today,
+        days if days <= UPCOMING_DAYS => DueStatus::Upcoming,
+        _ => DueStatus::Later
+    }
+}
+
+pub fn format_date(date: NaiveDate, today: NaiveDate) -> String {
+    match (date - today).num_days() {
+        -1 => "yesterday".to_string(),
+        0 => "today".to_string(),
+        1 => "tomorrow".to_string(),
+        _ if date.year() == today.year() => date.format("%b %d").to_string(),
+        _ => date.format("%Y-%m-%d").to_string()
+    }
+}
+
+pub fn format_timestamp(timestamp: NaiveDateTime, today: NaiveDate) -> String {
+    format!("{} {}", format_date(timestamp.date(), today), timestamp.format("%H:%M"))
+}
+
+// tracked time like `45m` or `3h05m`
+pub fn format_duration(duration: Duration) -> String {
+    let minutes = duration.num_minutes();
+    match minutes < 60 {
+        true => format!("{minutes}m"),
+        false => format!("{}h{:02}m", minutes / 60, minutes % 60)
+    }
+}
+
+// a running timer like `0:12:03`
+pub fn format_clock(duration: Duration) -> String {
+    let seconds = duration.num_seconds();
+    format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+// understands `YYYY-MM-DD`, `today`, `tomorrow`, `yesterday`, weekday names for their next
+// occurrence and offsets like `+3d`, `-1w` or `+2m`
+pub fn parse_date(input: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let input = input.trim().to_lowercase();
+
+    match input.as_str() {
+        "today" => return Some(today),
+        "tomorrow" => return today.checked_add_days(Days::new(1)),
+        "yesterday" => return today.checked_sub_days(Days::new(1)),
+        _ => {}
+    }
+
+    if let Ok(date) = NaiveDate::parse_from_str(&input, "%Y-%m-%d") {
+        return Some(date);
+    }
+
+    if let Ok(weekday) = input.parse::<Weekday>() {
+        return next_weekday(today, weekday);
+    }
+
+    parse_offset(&input, today)
+}
+
+// ISO 8601 basic format like iCalendar and Taskwarrior write it: a UTC timestamp like
+// `20240501T093000Z`, which is turned into local time, a floating one without the `Z` or a
+// date, which is taken at midnight
+pub fn parse_basic_timestamp(value: &str) -> Option<NaiveDateTime> {
+    if let Some(utc) = value.strip_suffix('Z') {
+        let utc = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some(Utc.from_utc_datetime(&utc).with_timezone(&Local).naive_local());
+    }
+
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()
+        .or_else(|| NaiveDate::parse_from_str(value, "%Y%m%d").ok()?.and_hms_opt(0, 0, 0))
+}
+
+pub fn next_weekday(date: NaiveDate, weekday: Weekday) -> Option<NaiveDate> {
+    let days_ahead = (weekday.num_days_from_monday() + 7 - date.weekday().num_days_from_monday()) % 7;
+    let days_ahead = if days_ahead == 0 { 7 } else { days_ahead };
+
+    date.checked_add_days(Days::new(days_ahead as u64))
+}
+
+fn parse_offset(input: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let (negative, input) = match input.chars().next()? {
+        '+' => (false, &input[1..]),
+        '-' => (true, &input[1..]),
+        _ => (false, input)
+    };
+
+    let unit = input.chars().last()?;
+    let amount: u32 = input[..input.len() - unit.len_utf8()].parse().ok()?;
+
+    match (unit, negative) {
+        ('d', false) => today.checked_add_days(Days::new(amount as u64)),
+        ('d', true) => today.checked_sub_days(Days::new(amount as u64)),
+        ('w', false) => today.checked_add_days(Days::new(amount as u64 * 7)),
+        ('w', true) => today.checked_sub_days(Days::new(amount as u64 * 7)),
+        ('m', false) => today.checked_add_months(Months::new(amount)),
+        ('m', true) => today.checked_sub_months(Months::new(amount)),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_basic_timestamps() {
+        let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+
+        assert_eq!(parse_basic_timestamp("20240501"), date.and_hms_opt(0, 0, 0));
+        assert_eq!(parse_basic_timestamp("20240501T093000"), date.and_hms_opt(9, 30, 0));
+        let utc = date.and_hms_opt(9, 30, 0).unwrap();
+        assert_eq!(parse_basic_timestamp("20240501T093000Z"), Some(Utc.from_utc_datetime(&utc).with_timezone(&Local).naive_local()));
+        assert_eq!(parse_basic_timestamp("2024-05-01"), None);
+    }
+}
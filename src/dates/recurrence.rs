@@ -0,0 +1,174 @@
+use std::{fmt::Display, str::FromStr};
+
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+
+// How a task repeats, written as `daily`, `weekly:mon,thu`, `monthly`, `monthly:31` or `after:3d`.
+// The written form is what users type and what gets stored.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Recurrence {
+    Daily,
+    // on the given weekdays, on the weekday of the due date if there are none
+    Weekly(Vec<Weekday>),
+    // on the given day of month or else on the day of the due date, on the last day in shorter
+    // months
+    Monthly(Option<u32>),
+    // the given number of days after an occurrence was completed
+    AfterCompletion(u64)
+}
+
+impl Recurrence {
+    // The rule the next occurrence takes over. A monthly rule without a day keeps the day of
+    // the first due date, that way a task due on the 31st goes back to the 31st after February.
+    pub fn pinned(self, due_date: Option<NaiveDate>) -> Self {
+        match (self, due_date) {
+            (Recurrence::Monthly(None), Some(due_date)) => Recurrence::Monthly(Some(due_date.day())),
+            (recurrence, _) => recurrence
+        }
+    }
+
+    // due date of the occurrence following one that was due on `due_date` and completed on
+    // `completed_on`. It stays on the schedule and comes after both dates, an early completion
+    // changes nothing and a late one skips the occurrences that passed in the meantime.
+    pub fn next_due_date(&self, due_date: Option<NaiveDate>, completed_on: NaiveDate) -> Option<NaiveDate> {
+        let anchor = due_date.map_or(completed_on, |due_date| due_date.max(completed_on));
+        let base = due_date.unwrap_or(completed_on);
+
+        match self {
+            Recurrence::Daily => anchor.checked_add_days(Days::new(1)),
+            Recurrence::Weekly(weekdays) => {
+                let weekdays = match weekdays.is_empty() {
+                    true => vec![base.weekday()],
+                    false => weekdays.clone()
+                };
+
+                (1..=7)
+                    .filter_map(|days| anchor.checked_add_days(Days::new(days)))
+                    .find(|date| weekdays.contains(&date.weekday()))
+            },
+            Recurrence::Monthly(day) => (0..=2)
+                .filter_map(|months| day_of_month(anchor, months, day.unwrap_or(base.day())))
+                .find(|date| *date > anchor),
+            Recurrence::AfterCompletion(days) => completed_on.checked_add_days(Days::new(*days))
+        }
+    }
+}
+
+// `day` in the month `months` after the month of `date`, the last day if the month is shorter
+fn day_of_month(date: NaiveDate, months: u32, day: u32) -> Option<NaiveDate> {
+    let first = date.with_day(1)?.checked_add_months(Months::new(months))?;
+    let last = first.checked_add_months(Months::new(1))?.pred_opt()?;
+
+    first.with_day(day.min(last.day()))
+}
+
+impl FromStr for Recurrence {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input = input.trim().to_lowercase();
+        let (kind, argument) = input.split_once(':').unwrap_or((&input, ""));
+
+        match (kind, argument) {
+            ("daily", "") => Ok(Recurrence::Daily),
+            ("weekly", weekdays) => {
+                let mut weekdays = weekdays.split(',')
+                    .filter(|weekday| !weekday.is_empty())
+                    .map(|weekday| weekday.parse::<Weekday>().map_err(|_| format!("unknown weekday {weekday}")))
+                    .collect::<Result<Vec<_>, _>>()?;
+                weekdays.sort_by_key(|weekday| weekday.num_days_from_monday());
+                weekdays.dedup();
+
+                Ok(Recurrence::Weekly(weekdays))
+            },
+            ("monthly", "") => Ok(Recurrence::Monthly(None)),
+            ("monthly", day) => day.parse()
+                .ok()
+                .filter(|day| (1..=31).contains(day))
+                .map(|day| Recurrence::Monthly(Some(day)))
+                .ok_or_else(|| "expected a day of month like monthly:31".to_string()),
+            ("after", days) => days.strip_suffix('d')
+                .and_then(|days| days.parse().ok())
+                .filter(|days| *days > 0)
+                .map(Recurrence::AfterCompletion)
+                .ok_or_else(|| "expected a number of days like after:3d".to_string()),
+            _ => Err(format!("unknown rule {input}, try daily, weekly:mon,thu, monthly or after:3d"))
+        }
+    }
+}
+
+impl Display for Recurrence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Recurrence::Daily => f.write_str("daily"),
+            Recurrence::Weekly(weekdays) if weekdays.is_empty() => f.write_str("weekly"),
+            Recurrence::Weekly(weekdays) => {
+                let weekdays: Vec<String> = weekdays.iter().map(|weekday| weekday.to_string().to_lowercase()).collect();
+                write!(f, "weekly:{}", weekdays.join(","))
+            },
+            Recurrence::Monthly(None) => f.write_str("monthly"),
+            Recurrence::Monthly(Some(day)) => write!(f, "monthly:{day}"),
+            Recurrence::AfterCompletion(days) => write!(f, "after:{days}d")
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn parses_what_it_writes() {
+        for input in ["daily", "weekly", "weekly:mon,thu", "monthly", "monthly:31", "after:3d"] {
+            assert_eq!(input.parse::<Recurrence>().unwrap().to_string(), input);
+        }
+
+        assert_eq!("Weekly:thu,mon,thu".parse(), Ok(Recurrence::Weekly(vec![Weekday::Mon, Weekday::Thu])));
+    }
+
+    #[test]
+    fn rejects_bad_rules() {
+        for input in ["yearly", "daily:2", "weekly:funday", "monthly:0", "monthly:32", "after:0d", "after:3"] {
+            assert!(input.parse::<Recurrence>().is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn monthly_keeps_the_day_across_shorter_months() {
+        let recurrence = Recurrence::Monthly(None).pinned(Some(date(2024, 1, 31)));
+        let mut due_date = date(2024, 1, 31);
+        let mut due_dates = vec![];
+        for _ in 0..4 {
+            due_date = recurrence.next_due_date(Some(due_date), due_date).unwrap();
+            due_dates.push(due_date);
+        }
+
+        assert_eq!(due_dates, [date(2024, 2, 29), date(2024, 3, 31), date(2024, 4, 30), date(2024, 5, 31)]);
+    }
+
+    #[test]
+    fn early_completions_keep_the_schedule() {
+        let due_date = Some(date(2024, 5, 10));
+        let completed_on = date(2024, 5, 1);
+
+        assert_eq!(Recurrence::Daily.next_due_date(due_date, completed_on), Some(date(2024, 5, 11)));
+        assert_eq!(Recurrence::Monthly(None).next_due_date(due_date, completed_on), Some(date(2024, 6, 10)));
+        assert_eq!(Recurrence::AfterCompletion(3).next_due_date(due_date, completed_on), Some(date(2024, 5, 4)));
+    }
+
+    #[test]
+    fn late_completions_skip_passed_occurrences() {
+        // a friday
+        let due_date = Some(date(2024, 5, 3));
+        let completed_on = date(2024, 5, 15);
+
+        assert_eq!(Recurrence::Daily.next_due_date(due_date, completed_on), Some(date(2024, 5, 16)));
+        assert_eq!(Recurrence::Weekly(vec![]).next_due_date(due_date, completed_on), Some(date(2024, 5, 17)));
+        assert_eq!(Recurrence::Weekly(vec![Weekday::Mon]).next_due_date(due_date, completed_on), Some(date(2024, 5, 20)));
+        assert_eq!(Recurrence::Monthly(Some(3)).next_due_date(due_date, completed_on), Some(date(2024, 6, 3)));
+    }
+}
@@ -0,0 +1,264 @@
+// iCalendar files as described in RFC 5545: components like `VCALENDAR` or `VTODO` between
+// `BEGIN:` and `END:` lines holding properties like `SUMMARY;LANGUAGE=en:call mom`. Long lines
+// are folded, a line starting with a space continues the one before.
+#[derive(Default)]
+pub struct Component {
+    pub name: String,
+    pub properties: Vec<Property>,
+    pub components: Vec<Component>
+}
+
+pub struct Property {
+    // upper case, names don't depend on case
+    pub name: String,
+    pub params: Vec<(String, String)>,
+    // as written, text values still have to be unescaped
+    pub value: String
+}
+
+// lines are at most this many bytes long without the line break
+const LINE_LENGTH: usize = 75;
+
+// `\`, `;` and `,` are escaped with a backslash and line breaks become `\n`
+pub fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            },
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {},
+            _ => escaped.push(c)
+        }
+    }
+
+    escaped
+}
+
+pub fn unescape_text(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n' | 'N') => text.push('\n'),
+                Some(escaped) => text.push(escaped),
+                None => {}
+            },
+            _ => text.push(c)
+        }
+    }
+
+    text
+}
+
+// joins folded lines
+fn unfold(content: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for line in content.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continued), Some(last)) => last.push_str(continued),
+            _ => lines.push(line.to_string())
+        }
+    }
+
+    lines
+}
+
+// None for lines without a value
+fn parse_property(line: &str) -> Option<Property> {
+    let name_end = line.find([';', ':'])?;
+    let mut property = Property {
+        name: line[..name_end].to_uppercase(),
+        params: vec![],
+        value: String::new()
+    };
+
+    // parameter values may be quoted to hold `;` and `:`
+    let mut rest = &line[name_end..];
+    while let Some(params) = rest.strip_prefix(';') {
+        let (name, after_name) = params.split_once('=')?;
+        let (value, after_value) = match after_name.strip_prefix('"') {
+            Some(quoted) => {
+                let (value, after_quote) = quoted.split_once('"')?;
+                (value, after_quote)
+            },
+            None => after_name.split_at(after_name.find([';', ':'])?)
+        };
+
+        property.params.push((name.to_uppercase(), value.to_string()));
+        rest = after_value;
+    }
+
+    property.value = rest.strip_prefix(':')?.to_string();
+    Some(property)
+}
+
+// a line break followed by a space after every LINE_LENGTH bytes, characters stay whole
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + line.len() / LINE_LENGTH * 3 + 2);
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > LINE_LENGTH {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+
+    folded + "\r\n"
+}
+
+impl Property {
+    pub fn new(name: &str, value: &str) -> Self {
+        Self { name: name.to_string(), params: vec![], value: value.to_string() }
+    }
+
+    pub fn with_param(mut self, name: &str, value: &str) -> Self {
+        self.params.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter().find(|(param, _)| param == name).map(|(_, value)| value.as_str())
+    }
+
+    fn render(&self) -> String {
+        let mut line = self.name.clone();
+        for (name, value) in &self.params {
+            match value.contains([';', ':', ',']) {
+                true => line.push_str(&format!(";{name}=\"{value}\"")),
+                false => line.push_str(&format!(";{name}={value}"))
+            }
+        }
+        line.push(':');
+        line.push_str(&self.value);
+
+        fold(&line)
+    }
+}
+
+// a finished component goes into the one around it or to the top
+fn close(component: Component, open: &mut [Component], finished: &mut Vec<Component>) {
+    match open.last_mut() {
+        Some(parent) => parent.components.push(component),
+        None => finished.push(component)
+    }
+}
+
+impl Component {
+    pub fn new(name: &str) -> Self {
+        Self { name: name.to_string(), ..Default::default() }
+    }
+
+    pub fn property(&self, name: &str) -> Option<&Property> {
+        self.properties.iter().find(|property| property.name == name)
+    }
+
+    // the components at the top of the file, usually a single `VCALENDAR`. Lines that can't
+    // be read are skipped and components that aren't closed end with the file.
+    pub fn parse(content: &str) -> Vec<Self> {
+        let mut finished = vec![];
+        let mut open: Vec<Component> = vec![];
+
+        for property in unfold(content).iter().filter_map(|line| parse_property(line)) {
+            match property.name.as_str() {
+                "BEGIN" => open.push(Component::new(&property.value.to_uppercase())),
+                "END" => {
+                    if let Some(component) = open.pop() {
+                        close(component, &mut open, &mut finished);
+                    }
+                },
+                _ => {
+                    if let Some(component) = open.last_mut() {
+                        component.properties.push(property);
+                    }
+                }
+            }
+        }
+
+        while let Some(component) = open.pop() {
+            close(component, &mut open, &mut finished);
+        }
+
+        finished
+    }
+
+    // with CRLF line breaks like the RFC asks for
+    pub fn render(&self) -> String {
+        let mut content = Property::new("BEGIN", &self.name).render();
+        for property in &self.properties {
+            content.push_str(&property.render());
+        }
+        for component in &self.components {
+            content.push_str(&component.render());
+        }
+        content.push_str(&Property::new("END", &self.name).render());
+
+        content
+    }
+
+    // every component of that name in this one and below, in the order of the file
+    pub fn find_all(&self, name: &str) -> Vec<&Component> {
+        self.components.iter()
+            .flat_map(|component| {
+                let mut found: Vec<&Component> = vec![];
+                if component.name == name {
+                    found.push(component);
+                }
+                found.extend(component.find_all(name));
+                found
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_text() {
+        let text = "call mom; then dad, maybe\nback\\slash";
+        let escaped = escape_text(text);
+
+        assert_eq!(escaped, r"call mom\; then dad\, maybe\nback\\slash");
+        assert_eq!(unescape_text(&escaped), text);
+        assert_eq!(unescape_text("a\\Nb"), "a\nb");
+    }
+
+    #[test]
+    fn folds_long_lines_without_splitting_characters() {
+        let value = "ä".repeat(60);
+        let rendered = Property::new("SUMMARY", &value).render();
+
+        assert!(rendered.split("\r\n").all(|line| line.len() <= LINE_LENGTH));
+        assert_eq!(unfold(&rendered), [format!("SUMMARY:{value}")]);
+    }
+
+    #[test]
+    fn reads_params_and_nesting() {
+        let content = "BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\nSUMMARY;LANGUAGE=en:call\r\n  mom\r\nRELATED-TO;RELTYPE=\"PA:RENT\":uid\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+        let calendars = Component::parse(content);
+
+        assert_eq!(calendars.len(), 1);
+        let todos = calendars[0].find_all("VTODO");
+        assert_eq!(todos.len(), 1);
+        assert_eq!(todos[0].property("SUMMARY").unwrap().value, "call mom");
+        assert_eq!(todos[0].property("SUMMARY").unwrap().param("LANGUAGE"), Some("en"));
+        assert_eq!(todos[0].property("RELATED-TO").unwrap().param("RELTYPE"), Some("PA:RENT"));
+
+        assert_eq!(calendars[0].render(), content.replace("call\r\n  mom", "call mom"));
+    }
+
+    #[test]
+    fn closes_unfinished_components() {
+        let calendars = Component::parse("BEGIN:VCALENDAR\nBEGIN:VTODO\nSUMMARY:cut off");
+
+        assert_eq!(calendars.len(), 1);
+        assert_eq!(calendars[0].find_all("VTODO").len(), 1);
+    }
+}
@@ -0,0 +1,304 @@
+use chrono::NaiveDate;
+
+use crate::dates::Recurrence;
+
+// indentation of nested items when the file doesn't show its own yet
+const DEFAULT_INDENT: usize = 2;
+
+// A Markdown checklist like `- [ ] [In Progress] text due:2024-05-01`, everything that isn't
+// a checklist item is kept as it is so writing the file back only changes what was edited.
+#[derive(Clone)]
+pub struct Checklist {
+    // lines in front of the first item
+    pub head: Vec<String>,
+    pub items: Vec<ChecklistItem>,
+    indent: usize,
+    trailing_newline: bool
+}
+
+#[derive(Clone)]
+pub struct ChecklistItem {
+    pub depth: usize,
+    pub checked: bool,
+    pub badge: Option<String>,
+    pub text: String,
+    // the indented block right below the item
+    pub note: Option<String>,
+    pub due_date: Option<NaiveDate>,
+    pub start_date: Option<NaiveDate>,
+    pub recurrence: Option<Recurrence>,
+    // `-`, `*` or `+`
+    pub marker: char,
+    // lines after the item (and its note) that aren't part of the checklist
+    pub trailing: Vec<String>
+}
+
+impl Default for ChecklistItem {
+    fn default() -> Self {
+        Self {
+            depth: 0,
+            checked: false,
+            badge: None,
+            text: String::new(),
+            note: None,
+            due_date: None,
+            start_date: None,
+            recurrence: None,
+            marker: '-',
+            trailing: vec![]
+        }
+    }
+}
+
+fn leading_spaces(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+// note lines that would read as an item get a `\` in front of the marker, lines already
+// starting with backslashes get one more so they come back unchanged
+fn looks_like_item(line: &str) -> bool {
+    let indent = leading_spaces(line);
+    parse_item(line[indent..].trim_start_matches('\\')).is_some()
+}
+
+fn escape_note_line(line: &str) -> String {
+    let indent = leading_spaces(line);
+    match looks_like_item(line) {
+        true => format!("{}\\{}", &line[..indent], &line[indent..]),
+        false => line.to_string()
+    }
+}
+
+fn unescape_note_line(line: &str) -> String {
+    let indent = leading_spaces(line);
+    match looks_like_item(line) && line[indent..].starts_with('\\') {
+        true => format!("{}{}", &line[..indent], &line[indent + 1..]),
+        false => line.to_string()
+    }
+}
+
+// `- [ ] rest`, returns the indentation and the item without depth or note
+fn parse_item(line: &str) -> Option<(usize, ChecklistItem)> {
+    let indent = leading_spaces(line);
+    let rest = &line[indent..];
+
+    let marker = rest.chars().next().filter(|marker| matches!(marker, '-' | '*' | '+'))?;
+    let checked = match rest.get(1..5)? {
+        " [ ]" => false,
+        " [x]" | " [X]" => true,
+        _ => return None
+    };
+
+    let rest = &rest[5..];
+    if !rest.is_empty() && !rest.starts_with(' ') {
+        return None;
+    }
+    let mut text = rest.trim_start();
+
+    // a badge prefix looks like `[In Progress] `, links like `[name](url)` don't count
+    let mut badge = None;
+    if let Some(end) = text.strip_prefix('[').and_then(|inner| inner.find(']')) {
+        let after = &text[end + 2..];
+        if end > 0 && (after.is_empty() || after.starts_with(' ')) {
+            badge = Some(text[1..end + 1].to_string());
+            text = after.trim_start();
+        }
+    }
+
+    // dates and the recurrence are trailing `due:`, `start:` and `repeat:` tokens
+    let mut due_date = None;
+    let mut start_date = None;
+    let mut recurrence = None;
+    loop {
+        let (before, token) = text.rsplit_once(' ').unwrap_or(("", text));
+        let date = |prefix| token.strip_prefix(prefix).and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok());
+        let rule = token.strip_prefix("repeat:").and_then(|rule| rule.parse::<Recurrence>().ok());
+
+        match (date("due:"), date("start:"), rule) {
+            (Some(date), _, _) if due_date.is_none() => due_date = Some(date),
+            (_, Some(date), _) if start_date.is_none() => start_date = Some(date),
+            (_, _, Some(rule)) if recurrence.is_none() => recurrence = Some(rule),
+            _ => break
+        }
+        text = before.trim_end();
+    }
+
+    Some((indent, ChecklistItem {
+        checked,
+        badge,
+        text: text.to_string(),
+        due_date,
+        start_date,
+        recurrence,
+        marker,
+        ..Default::default()
+    }))
+}
+
+impl Checklist {
+    // a checklist of nothing but the items
+    pub fn new(items: Vec<ChecklistItem>) -> Self {
+        Self {
+            head: vec![],
+            items,
+            indent: DEFAULT_INDENT,
+            trailing_newline: true
+        }
+    }
+
+    pub fn parse(content: &str) -> Self {
+        let mut checklist = Self {
+            head: vec![],
+            items: vec![],
+            indent: DEFAULT_INDENT,
+            trailing_newline: content.is_empty() || content.ends_with('\n')
+        };
+
+        // indentation of the items the next item could be nested in
+        let mut open_indents: Vec<usize> = vec![];
+        let mut current_indent = 0;
+        let mut blank_lines: Vec<String> = vec![];
+        let mut indent_found = false;
+
+        for line in content.lines() {
+            if let Some((indent, mut item)) = parse_item(line) {
+                while open_indents.last().is_some_and(|open| *open >= indent) {
+                    open_indents.pop();
+                }
+
+                if let (Some(parent_indent), false) = (open_indents.last(), indent_found) {
+                    checklist.indent = indent - parent_indent;
+                    indent_found = true;
+                }
+
+                checklist.flush_blank_lines(&mut blank_lines);
+                item.depth = open_indents.len();
+                open_indents.push(indent);
+                current_indent = indent;
+                checklist.items.push(item);
+                continue;
+            }
+
+            let Some(item) = checklist.items.last_mut() else {
+                checklist.head.push(line.to_string());
+                continue
+            };
+
+            if line.trim().is_empty() {
+                blank_lines.push(line.to_string());
+                continue;
+            }
+
+            // the note is the indented block right below the item, blank lines may be part of it
+            let is_note = leading_spaces(line) > current_indent && item.trailing.is_empty();
+            if !is_note {
+                checklist.flush_blank_lines(&mut blank_lines);
+                checklist.items.last_mut().unwrap().trailing.push(line.to_string());
+                continue;
+            }
+
+            let note_line = &line[leading_spaces(line).min(current_indent + 2)..];
+            let note = item.note.get_or_insert_with(String::new);
+            if !note.is_empty() {
+                note.push('\n');
+            }
+            for _ in blank_lines.drain(..) {
+                note.push('\n');
+            }
+            note.push_str(&unescape_note_line(note_line));
+        }
+        checklist.flush_blank_lines(&mut blank_lines);
+
+        checklist
+    }
+
+    fn flush_blank_lines(&mut self, blank_lines: &mut Vec<String>) {
+        let lines = match self.items.last_mut() {
+            Some(item) => &mut item.trailing,
+            None => &mut self.head
+        };
+
+        lines.append(blank_lines);
+    }
+
+    pub fn render(&self) -> String {
+        let mut lines: Vec<String> = self.head.clone();
+
+        for item in &self.items {
+            let indent = " ".repeat(item.depth * self.indent);
+            let check = if item.checked { 'x' } else { ' ' };
+
+            let mut line = format!("{indent}{} [{check}]", item.marker);
+            if let Some(badge) = &item.badge {
+                line.push_str(&format!(" [{badge}]"));
+            }
+            if !item.text.is_empty() {
+                line.push_str(&format!(" {}", item.text));
+            }
+            if let Some(due_date) = item.due_date {
+                line.push_str(&format!(" due:{due_date}"));
+            }
+            if let Some(start_date) = item.start_date {
+                line.push_str(&format!(" start:{start_date}"));
+            }
+            if let Some(recurrence) = &item.recurrence {
+                line.push_str(&format!(" repeat:{recurrence}"));
+            }
+            lines.push(line);
+
+            let note_lines = item.note.iter().flat_map(|note| note.lines());
+            for note_line in note_lines {
+                match note_line.is_empty() {
+                    true => lines.push(String::new()),
+                    false => lines.push(format!("{indent}  {}", escape_note_line(note_line)))
+                }
+            }
+
+            lines.extend(item.trailing.iter().cloned());
+        }
+
+        let mut content = lines.join("\n");
+        if self.trailing_newline && !content.is_empty() {
+            content.push('\n');
+        }
+        content
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_unknown_lines() {
+        let content = "# Tasks\n\n- [ ] call mom\nsome text\n  - [x] [Done] nested due:2024-05-01\n";
+        let checklist = Checklist::parse(content);
+
+        assert_eq!(checklist.items.len(), 2);
+        assert_eq!(checklist.items[1].depth, 1);
+        assert_eq!(checklist.items[1].badge.as_deref(), Some("Done"));
+        assert_eq!(checklist.items[1].due_date, NaiveDate::from_ymd_opt(2024, 5, 1));
+        assert_eq!(checklist.render(), content);
+    }
+
+    #[test]
+    fn notes_that_look_like_items_stay_notes() {
+        let note = "steps:\n- [ ] first\n  * [x] second\n\\- [ ] escaped already";
+        let item = ChecklistItem { text: "deploy".to_string(), note: Some(note.to_string()), ..Default::default() };
+        let content = Checklist::new(vec![item]).render();
+
+        let checklist = Checklist::parse(&content);
+        assert_eq!(checklist.items.len(), 1);
+        assert_eq!(checklist.items[0].note.as_deref(), Some(note));
+        assert_eq!(checklist.render(), content);
+    }
+
+    #[test]
+    fn links_are_no_badges() {
+        let checklist = Checklist::parse("- [ ] [docs](https://example.com) repeat:weekly:mon");
+
+        assert_eq!(checklist.items[0].badge, None);
+        assert_eq!(checklist.items[0].text, "[docs](https://example.com)");
+        assert!(checklist.items[0].recurrence.is_some());
+    }
+}
@@ -0,0 +1,3 @@
+pub mod markdown;
+pub mod ical;
+pub mod todotxt;
@@ -0,0 +1,152 @@
+use chrono::NaiveDate;
+
+// A line of a todo.txt file like `x 2024-05-02 2024-04-30 (A) call mom +family @phone due:2024-05-03`.
+// `+project` and `@context` tags and `key:value` extras stay in the description where they were
+// written, that way rendering the item gives back the line it was parsed from.
+#[derive(Default)]
+pub struct TodoItem {
+    pub done: bool,
+    // `A` to `Z`
+    pub priority: Option<char>,
+    pub completed_on: Option<NaiveDate>,
+    pub created_on: Option<NaiveDate>,
+    pub description: String
+}
+
+fn parse_date(token: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(token, "%Y-%m-%d").ok()
+}
+
+fn parse_priority(token: &str) -> Option<char> {
+    match token.as_bytes() {
+        [b'(', priority @ b'A'..=b'Z', b')'] => Some(*priority as char),
+        _ => None
+    }
+}
+
+// `key:value` with a plain key, URLs like `https://…` don't count
+pub fn parse_extra(token: &str) -> Option<(&str, &str)> {
+    let (key, value) = token.split_once(':')?;
+    let plain_key = !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+
+    match plain_key && !value.is_empty() && !value.starts_with("//") {
+        true => Some((key, value)),
+        false => None
+    }
+}
+
+impl TodoItem {
+    // None for blank lines
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut tokens = line.split(' ').filter(|token| !token.is_empty()).peekable();
+        tokens.peek()?;
+
+        let mut item = Self::default();
+        if tokens.peek() == Some(&"x") {
+            tokens.next();
+            item.done = true;
+        }
+
+        // finished items may keep their priority in front of the dates or after them
+        item.priority = tokens.peek().and_then(|token| parse_priority(token));
+        if item.priority.is_some() {
+            tokens.next();
+        }
+
+        // a single date of a finished item is the completion date
+        let mut dates = vec![];
+        while dates.len() < 2 {
+            let Some(date) = tokens.peek().and_then(|token| parse_date(token)) else {
+                break
+            };
+            dates.push(date);
+            tokens.next();
+        }
+        match (item.done, dates.as_slice()) {
+            (true, [completed_on, created_on]) => (item.completed_on, item.created_on) = (Some(*completed_on), Some(*created_on)),
+            (true, [completed_on]) => item.completed_on = Some(*completed_on),
+            (false, [created_on, ..]) => item.created_on = Some(*created_on),
+            _ => {}
+        }
+
+        if item.priority.is_none() && item.done {
+            item.priority = tokens.peek().and_then(|token| parse_priority(token));
+            if item.priority.is_some() {
+                tokens.next();
+            }
+        }
+
+        item.description = tokens.collect::<Vec<_>>().join(" ");
+        Some(item)
+    }
+
+    pub fn render(&self) -> String {
+        let mut tokens: Vec<String> = vec![];
+        if self.done {
+            tokens.push("x".to_string());
+        }
+        if let (false, Some(priority)) = (self.done, self.priority) {
+            tokens.push(format!("({priority})"));
+        }
+        // the creation date can't be told apart from a completion date on its own
+        if let (true, Some(completed_on)) = (self.done, self.completed_on.or(self.created_on)) {
+            tokens.push(completed_on.to_string());
+        }
+        if let Some(created_on) = self.created_on {
+            tokens.push(created_on.to_string());
+        }
+        if let (true, Some(priority)) = (self.done, self.priority) {
+            tokens.push(format!("({priority})"));
+        }
+        if !self.description.is_empty() {
+            tokens.push(self.description.clone());
+        }
+
+        tokens.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(year, month, day)
+    }
+
+    #[test]
+    fn round_trips_lines() {
+        for line in [
+            "(A) 2024-04-30 call mom +family @phone due:2024-05-03",
+            "x 2024-05-02 2024-04-30 (B) pay rent",
+            "x 2024-05-02 water plants",
+            "read https://example.com later"
+        ] {
+            assert_eq!(TodoItem::parse(line).unwrap().render(), line);
+        }
+    }
+
+    #[test]
+    fn reads_dates_and_priority() {
+        let item = TodoItem::parse("x (C) 2024-05-02 2024-04-30 pay rent").unwrap();
+        assert!(item.done);
+        assert_eq!(item.priority, Some('C'));
+        assert_eq!(item.completed_on, date(2024, 5, 2));
+        assert_eq!(item.created_on, date(2024, 4, 30));
+        assert_eq!(item.description, "pay rent");
+
+        let item = TodoItem::parse("2024-04-30 (A) not a priority").unwrap();
+        assert_eq!(item.priority, None);
+        assert_eq!(item.description, "(A) not a priority");
+
+        assert!(TodoItem::parse("   ").is_none());
+    }
+
+    #[test]
+    fn extras_need_a_plain_key() {
+        assert_eq!(parse_extra("due:2024-05-03"), Some(("due", "2024-05-03")));
+        assert_eq!(parse_extra("https://example.com"), None);
+        assert_eq!(parse_extra("due:"), None);
+        assert_eq!(parse_extra(":value"), None);
+    }
+}
@@ -1,23 +1,34 @@
 mod ui;
 mod command;
 mod store;
+mod dates;
+mod format;
+mod cli;
+mod transfer;
 // mod app;
 mod parser;
 
-use std::{path::PathBuf, fs::File, io::{Stdout, stdout}, fmt::Display, process::exit, sync::mpsc::{self, Receiver}, thread, time::Duration};
+use std::{path::Path, fs::{self, File}, io::{self, Read, Stdout, stdout}, fmt::Display, process::exit, sync::mpsc::{self, Receiver}, thread, time::{Duration, Instant}, collections::HashSet};
 
+use cli::{Args, Command, Format, TaskOrder};
 use command::KeyCommandComposer;
 use crossterm::{terminal::enable_raw_mode, event::{self, KeyCode}};
 use log::error;
-use ratatui::{prelude::{CrosstermBackend, Rect, Layout, Direction, Constraint}, Terminal, widgets::{Block, Borders, Paragraph, ListItem, List}, style::{Color, Style}};
+use ratatui::{prelude::{CrosstermBackend, Rect, Layout, Direction, Constraint, Alignment}, Terminal, widgets::{Block, Borders, Paragraph, ListItem, List}, style::{Color, Style, Modifier}, text::{Line, Span}};
 use rusqlite::Connection;
-use store::TaskStore;
-use ui::textarea::TextArea;
+use store::{TaskStore, Task, Conflict, Storage, SqliteStorage, MemoryStorage, MarkdownStorage, ImportMode};
+use ui::{textarea::TextArea, date_input_window::{DateInputWindow, DateField}, list_picker_window::{ListPickerWindow, ListPickerAction}, search_window::SearchWindow, badge_editor_window::BadgeEditorWindow, conflict_window::ConflictWindow, trash_window::TrashWindow, task_detail_window::TaskDetailWindow, sort_window::SortWindow, badge_select_window::BadgeSelectWindow, recurrence_input_window::RecurrenceInputWindow, dependency_window::DependencyWindow};
+use dates::DueStatus;
 
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
 type Frame<'a> = ratatui::Frame<'a, CrosstermBackend<Stdout>>;
 
+// how often the store looks for changes made by other processes
+const RELOAD_INTERVAL: Duration = Duration::from_millis(500);
+// how long a write waits while another process holds the database lock
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[derive(Clone, Copy)]
 enum LetterMode {
     Normal,
@@ -35,14 +46,20 @@ impl Display for LetterMode {
 
 struct LetterState {
     store: TaskStore,
-    mode: LetterMode
+    mode: LetterMode,
+    // shown in the status bar until the next key is pressed
+    message: Option<String>,
+    // the task list moves its cursor to this task on the next update
+    focus_task_id: Option<i64>
 }
 
 impl LetterState {
     fn new(store: TaskStore) -> Self {
         Self {
             store,
-            mode: LetterMode::Normal
+            mode: LetterMode::Normal,
+            message: None,
+            focus_task_id: None
         }
     }
 }
@@ -51,27 +68,220 @@ trait Window {
     fn handle_event(&mut self, state: &mut LetterState, event: LetterEvent) -> WindowCommand;
     fn update(&mut self, state: &mut LetterState) -> WindowCommand;
     fn draw(&self, state: &LetterState, frame: &mut Frame, rect: Rect);
+
+    // popups are drawn above the other windows and receive every key as raw input
+    fn is_popup(&self) -> bool {
+        false
+    }
 }
 
 struct TaskListWindow {
     text_area: TextArea<LetterState, LetterCommand>,
+    folded_task_ids: HashSet<i64>,
+    // task index for every line of the text area, folded subtasks have no line
+    visible_tasks: Vec<usize>,
+    // task id for every line, finds the task under the cursor again after a reload
+    visible_task_ids: Vec<Option<i64>>,
+    store_generation: u64,
 }
 
 impl TaskListWindow {
     fn new(store: &TaskStore) -> Self {
-        let text_area = TextArea::new(store.tasks.iter().map(|task| task.text.clone()).collect());
-        return TaskListWindow { text_area }
+        let mut text_area = TextArea::new(vec![]);
+        text_area.disallow_line_breaks();
+
+        let mut task_list_window = TaskListWindow {
+            text_area,
+            folded_task_ids: HashSet::new(),
+            visible_tasks: vec![],
+            visible_task_ids: vec![],
+            store_generation: store.generation()
+        };
+
+        task_list_window.refresh(store, None);
+        task_list_window
+    }
+
+    fn task_index_at(&self, y: usize) -> Option<usize> {
+        self.visible_tasks.get(y).copied()
+    }
+
+    fn task_id_at(&self, store: &TaskStore, y: usize) -> Option<i64> {
+        self.task_index_at(y).and_then(|idx| store.tasks[idx].id)
+    }
+
+    fn is_folded(&self, task: &Task) -> bool {
+        task.id.is_some_and(|task_id| self.folded_task_ids.contains(&task_id))
+    }
+
+    // rebuilds the lines from the store, the cursor stays on `focus_task_idx` if given
+    // and on the same line otherwise
+    fn refresh(&mut self, store: &TaskStore, focus_task_idx: Option<usize>) {
+        let (x, y) = self.text_area.get_cursor();
+        let depths = store.task_depths();
+
+        let mut folded_depth: Option<usize> = None;
+        self.visible_tasks = vec![];
+        for (idx, task) in store.tasks.iter().enumerate() {
+            if let Some(depth) = folded_depth {
+                if depths[idx] > depth {
+                    continue;
+                }
+                folded_depth = None;
+            }
+
+            self.visible_tasks.push(idx);
+            if self.is_folded(task) {
+                folded_depth = Some(depths[idx]);
+            }
+        }
+
+        self.visible_task_ids = self.visible_tasks.iter()
+            .map(|idx| store.tasks[*idx].id)
+            .collect();
+
+        let lines = self.visible_tasks.iter()
+            .map(|idx| store.tasks[*idx].text.clone())
+            .collect();
+        let indents = self.visible_tasks.iter()
+            .map(|idx| depths[*idx] as u16 * 2)
+            .collect();
+
+        self.text_area.set_lines(lines);
+        self.text_area.set_indents(indents);
+        self.store_generation = store.generation();
+
+        let y = focus_task_idx
+            .and_then(|task_idx| self.visible_tasks.iter().position(|idx| *idx == task_idx))
+            .unwrap_or(y)
+            .min(self.text_area.lines.len() - 1);
+        let x = x.min(self.text_area.lines[y].len());
+        self.text_area.set_cursor((x, y));
+    }
+
+    // moves the cursor to the task, folds hiding it are opened
+    fn focus_task(&mut self, store: &TaskStore, task_id: i64) {
+        let task_idx = store.tasks.iter().position(|task| task.id == Some(task_id));
+        if let Some(task_idx) = task_idx {
+            let mut parent_id = store.tasks[task_idx].parent_id;
+            while let Some(id) = parent_id {
+                self.folded_task_ids.remove(&id);
+                parent_id = store.tasks.iter()
+                    .find(|task| task.id == Some(id))
+                    .and_then(|parent| parent.parent_id);
+            }
+        }
+
+        self.refresh(store, task_idx);
+    }
+
+    fn sync_line(&mut self, state: &mut LetterState, y: usize) -> WindowCommand {
+        let (Some(task_idx), Some(line)) = (self.task_index_at(y), self.text_area.lines.get(y)) else {
+            return None
+        };
+
+        let task = &state.store.tasks[task_idx];
+        let (Some(task_id), true) = (task.id, task.text != *line) else {
+            return None
+        };
+
+        match state.store.update_task_text(task_id, line) {
+            Ok(()) => None,
+            // their version gets loaded and the edit is offered next to it
+            Err(err) if err.is::<Conflict>() => {
+                let mine = line.clone();
+                if let Err(err) = state.store.fetch_data() {
+                    error!("couldn't reload after a conflict: {err}")
+                }
+
+                Some(_WindowCommand::OpenWindow(Box::new(ConflictWindow::new(task_id, mine))))
+            },
+            Err(_) => {
+                error!("couldn't update task text for task {task_id}");
+                None
+            }
+        }
+    }
+
+    fn handle_tree_command(&mut self, state: &mut LetterState, task_idx: usize, tree_cmd: TreeCommand) {
+        let store = &mut state.store;
+        let Some(task_id) = store.tasks[task_idx].id else {
+            return
+        };
+
+        match tree_cmd {
+            TreeCommand::Indent => {
+                if store.indent_task(task_id).is_err() {
+                    error!("couldn't indent task {task_id}")
+                }
+            },
+            TreeCommand::Outdent => {
+                if store.outdent_task(task_id).is_err() {
+                    error!("couldn't outdent task {task_id}")
+                }
+            },
+            TreeCommand::ToggleFold | TreeCommand::OpenFold | TreeCommand::CloseFold => {
+                let fold = match tree_cmd {
+                    TreeCommand::OpenFold => false,
+                    TreeCommand::CloseFold => true,
+                    _ => !self.folded_task_ids.contains(&task_id)
+                };
+
+                if fold && store.has_subtasks(task_idx) {
+                    self.folded_task_ids.insert(task_id);
+                } else {
+                    self.folded_task_ids.remove(&task_id);
+                }
+            },
+            TreeCommand::OpenAllFolds => self.folded_task_ids.clear(),
+            TreeCommand::CloseAllFolds => {
+                self.folded_task_ids = (0..store.tasks.len())
+                    .filter(|idx| store.has_subtasks(*idx))
+                    .filter_map(|idx| store.tasks[idx].id)
+                    .collect();
+            },
+        }
+
+        self.refresh(&state.store, Some(task_idx));
     }
 }
 
 impl Window for TaskListWindow {
-    fn update(&mut self, _state: &mut LetterState) -> WindowCommand {
+    fn update(&mut self, state: &mut LetterState) -> WindowCommand {
+        if let Some(task_id) = state.focus_task_id.take() {
+            self.focus_task(&state.store, task_id);
+        } else if self.store_generation != state.store.generation() {
+            // the tasks were reloaded, the cursor stays with its task if that's still around
+            let (_, y) = self.text_area.get_cursor();
+            match self.visible_task_ids.get(y).copied().flatten() {
+                Some(task_id) => self.focus_task(&state.store, task_id),
+                None => self.refresh(&state.store, None)
+            }
+        }
+
+        // blockers change their badges without the lines being rebuilt
+        let line_styles = self.visible_tasks.iter()
+            .map(|idx| {
+                let task = &state.store.tasks[*idx];
+                match (state.store.is_done(task), state.store.is_blocked(task)) {
+                    (true, _) => Style::default().fg(Color::DarkGray).add_modifier(Modifier::CROSSED_OUT),
+                    (false, true) => Style::default().fg(Color::DarkGray),
+                    (false, false) => Style::default()
+                }
+            })
+            .collect();
+        self.text_area.set_line_styles(line_styles);
+
         None
     }
 
     fn draw(&self, state: &LetterState, frame: &mut Frame, rect: Rect) {
+        let title = state.store.current_list()
+            .map(|list| list.name.clone())
+            .unwrap_or_else(|| "Tasks".to_string());
+
         let block = Block::default()
-            .title("Tasks")
+            .title(title)
             .borders(Borders::ALL);
 
         let block_rect = block.inner(rect);
@@ -84,16 +294,59 @@ impl Window for TaskListWindow {
             .max()
             .unwrap_or(0) as u16;
 
+        let today = dates::today();
+        // recurring tasks get a marker in front of their due date
+        let due_dates: Vec<Option<String>> = self.visible_tasks.iter()
+            .map(|idx| {
+                let task = &state.store.tasks[*idx];
+                let due_date = task.due_date.map(|due_date| dates::format_date(due_date, today));
+                match (task.recurrence.is_some(), due_date) {
+                    (true, Some(due_date)) => Some(format!("↻ {due_date}")),
+                    (true, None) => Some("↻".to_string()),
+                    (false, due_date) => due_date
+                }
+            })
+            .collect();
+
+        let widest_due_date = due_dates.iter()
+            .flatten()
+            .map(|due_date| due_date.chars().count() as u16 + 1)
+            .max()
+            .unwrap_or(0);
+
+        // only tasks that were worked on show their time
+        let now = dates::now();
+        let running_task_id = state.store.running_timer().map(|entry| entry.task_id);
+        let tracked_times: Vec<Option<String>> = self.visible_tasks.iter()
+            .map(|idx| {
+                let task_id = state.store.tasks[*idx].id?;
+                let tracked_time = state.store.tracked_time(task_id, now);
+                match tracked_time.is_zero() && running_task_id != Some(task_id) {
+                    true => None,
+                    false => Some(dates::format_duration(tracked_time))
+                }
+            })
+            .collect();
+
+        let widest_tracked_time = tracked_times.iter()
+            .flatten()
+            .map(|tracked_time| tracked_time.len() as u16 + 1)
+            .max()
+            .unwrap_or(0);
+
         let editor_layout = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([
                 Constraint::Length(widest_badge_used),
                 Constraint::Length(1),
-                Constraint::Length(rect.width - widest_badge_used)
+                Constraint::Length(widest_due_date),
+                Constraint::Length(widest_tracked_time),
+                Constraint::Length(rect.width.saturating_sub(widest_badge_used + 1 + widest_due_date + widest_tracked_time))
             ]).split(rect);
 
-        let task_status_list: Vec<ListItem> = state.store.tasks.iter()
-            .map(|task| {
+        let task_status_list: Vec<ListItem> = self.visible_tasks.iter()
+            .map(|idx| {
+                let task = &state.store.tasks[*idx];
                 let badge = state.store.get_badge(task);
                 let color = badge.map(|badge| badge.color).unwrap_or_else(|| Color::Black);
                 let name = badge.map(|badge| badge.name.clone()).unwrap_or_else(|| String::new());
@@ -101,28 +354,192 @@ impl Window for TaskListWindow {
                     .style(Style::default().bg(color))
             }).collect();
 
+        let due_date_list: Vec<ListItem> = self.visible_tasks.iter()
+            .zip(due_dates)
+            .map(|(idx, due_date)| {
+                let Some(due_date_str) = due_date else {
+                    return ListItem::new("");
+                };
+
+                let color = match state.store.tasks[*idx].due_date.map(|due_date| dates::due_status(due_date, today)) {
+                    Some(DueStatus::Overdue) => Color::LightRed,
+                    Some(DueStatus::Today) => Color::LightYellow,
+                    Some(DueStatus::Upcoming) => Color::LightGreen,
+                    Some(DueStatus::Later) | None => Color::Gray
+                };
+
+                ListItem::new(due_date_str).style(Style::default().fg(color))
+            }).collect();
+
+        // the running one stands out
+        let tracked_time_list: Vec<ListItem> = self.visible_tasks.iter()
+            .zip(tracked_times)
+            .map(|(idx, tracked_time)| {
+                let color = match state.store.tasks[*idx].id == running_task_id {
+                    true => Color::LightGreen,
+                    false => Color::DarkGray
+                };
+
+                ListItem::new(tracked_time.unwrap_or_default()).style(Style::default().fg(color))
+            }).collect();
+
         frame.render_widget(List::new(task_status_list), editor_layout[0]);
-        self.text_area.draw(frame, editor_layout[2]);
+        frame.render_widget(List::new(due_date_list), editor_layout[2]);
+        frame.render_widget(List::new(tracked_time_list), editor_layout[3]);
+        self.text_area.draw(frame, editor_layout[4]);
+
+        let text_rect = editor_layout[4];
+        self.visible_tasks.iter()
+            .enumerate()
+            .filter(|(y, idx)| *y < text_rect.height as usize && self.is_folded(&state.store.tasks[**idx]))
+            .for_each(|(y, idx)| {
+                let hidden_count = state.store.subtree_end(*idx) - idx - 1;
+                let offset = self.text_area.get_indent(y) + self.text_area.lines[y].len() as u16 + 1;
+                if offset >= text_rect.width {
+                    return;
+                }
+
+                let fold_paragraph = Paragraph::new(format!("[+{hidden_count}]"))
+                    .style(Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC));
+                frame.render_widget(fold_paragraph, Rect::new(text_rect.x + offset, text_rect.y + y as u16, text_rect.width - offset, 1));
+            });
     }
 
     fn handle_event(&mut self, state: &mut LetterState, event: LetterEvent) -> WindowCommand {
 
         let (_, y) = self.text_area.get_cursor();
-        if let LetterEvent::CommandEvent(LetterCommand::Delete(DeleteCommand::DeleteLine)) = event {
-            if state.store.tasks.len() > y {
-                if let Err(_) = state.store.delete_task(y as i64) {
-                    error!("couldn't delete task at {y}")
+        let task_idx = self.task_index_at(y);
+        let task_id = self.task_id_at(&state.store, y);
+        match event {
+            LetterEvent::CommandEvent(LetterCommand::Delete(DeleteCommand::DeleteLine)) => {
+                if let Some(task_id) = task_id {
+                    match state.store.delete_task(task_id) {
+                        Ok(()) => state.message = Some("moved to the trash, <space>t to restore".to_string()),
+                        Err(_) => error!("couldn't delete task {task_id}")
+                    }
+
+                    // subtasks of the deleted task move up and may have been folded away
+                    self.refresh(&state.store, None);
+                    return None;
                 }
-            }
+            },
+            LetterEvent::CommandEvent(LetterCommand::Tree(tree_cmd)) => {
+                if let Some(task_idx) = task_idx {
+                    self.handle_tree_command(state, task_idx, tree_cmd);
+                }
+                return None;
+            },
+            LetterEvent::CommandEvent(LetterCommand::EditDate(field)) => {
+                let task_id = task_id?;
+                return Some(_WindowCommand::OpenWindow(Box::new(DateInputWindow::new(&state.store, task_id, field))));
+            },
+            LetterEvent::CommandEvent(LetterCommand::EditRecurrence) => {
+                let task_id = task_id?;
+                return Some(_WindowCommand::OpenWindow(Box::new(RecurrenceInputWindow::new(&state.store, task_id))));
+            },
+            LetterEvent::CommandEvent(LetterCommand::SelectBadge) => {
+                let task_id = task_id?;
+                return Some(_WindowCommand::OpenWindow(Box::new(BadgeSelectWindow::new(&state.store, task_id))));
+            },
+            LetterEvent::CommandEvent(LetterCommand::StartTimer) => {
+                let task_id = task_id?;
+                state.message = Some(match state.store.start_timer(task_id) {
+                    Ok(()) => "timer started, <space>C to stop it".to_string(),
+                    Err(err) => err.to_string()
+                });
+                return None;
+            },
+            LetterEvent::CommandEvent(LetterCommand::StopTimer) => {
+                state.message = Some(match state.store.stop_timer() {
+                    Ok(duration) => format!("timer stopped after {}", dates::format_duration(duration)),
+                    Err(err) => err.to_string()
+                });
+                return None;
+            },
+            LetterEvent::CommandEvent(LetterCommand::CycleBadge) => {
+                let task_id = task_id?;
+                match state.store.cycle_task_badge(task_id) {
+                    Ok(Some(next_id)) => {
+                        state.message = Some("created the next occurrence".to_string());
+                        state.focus_task_id = Some(next_id);
+                    },
+                    Ok(None) => {},
+                    Err(err) => state.message = Some(err.to_string())
+                }
+                return None;
+            },
+            LetterEvent::CommandEvent(LetterCommand::ShowDependencies) => {
+                let task_id = task_id?;
+                return Some(_WindowCommand::OpenWindow(Box::new(DependencyWindow::new(task_id))));
+            },
+            LetterEvent::CommandEvent(LetterCommand::Undo) => {
+                let result = state.store.undo();
+                state.message = Some(history_message("undone", result));
+                return None;
+            },
+            LetterEvent::CommandEvent(LetterCommand::Redo) => {
+                let result = state.store.redo();
+                state.message = Some(history_message("redone", result));
+                return None;
+            },
+            LetterEvent::CommandEvent(LetterCommand::Search) => {
+                return Some(_WindowCommand::OpenWindow(Box::new(SearchWindow::new())));
+            },
+            LetterEvent::CommandEvent(LetterCommand::ShowDetails) => {
+                let task_id = task_id?;
+                return Some(_WindowCommand::OpenWindow(Box::new(TaskDetailWindow::new(task_id))));
+            },
+            LetterEvent::CommandEvent(LetterCommand::SortTasks) => {
+                return Some(_WindowCommand::OpenWindow(Box::new(SortWindow::new())));
+            },
+            LetterEvent::CommandEvent(LetterCommand::OpenTrash) => {
+                return Some(_WindowCommand::OpenWindow(Box::new(TrashWindow::new(&state.store))));
+            },
+            LetterEvent::CommandEvent(LetterCommand::ManageBadges) => {
+                return Some(_WindowCommand::OpenWindow(Box::new(BadgeEditorWindow::new())));
+            },
+            LetterEvent::CommandEvent(LetterCommand::PickList) => {
+                return Some(_WindowCommand::OpenWindow(Box::new(ListPickerWindow::new(&state.store, ListPickerAction::Switch))));
+            },
+            LetterEvent::CommandEvent(LetterCommand::MoveTaskToList) => {
+                let task_id = task_id?;
+                return Some(_WindowCommand::OpenWindow(Box::new(ListPickerWindow::new(&state.store, ListPickerAction::MoveTask(task_id)))));
+            },
+            _ => {}
+        }
+
+        let window_cmd = self.text_area.handle_letter_event(event);
+        match event {
+            LetterEvent::CommandEvent(LetterCommand::Delete(DeleteCommand::DeleteChar)) | LetterEvent::RawKeyInputEvent(KeyCode::Esc) => {
+                if let Some(conflict_cmd) = self.sync_line(state, y) {
+                    // leaves insert mode on the way, the popup takes every key
+                    state.mode = LetterMode::Normal;
+                    return Some(conflict_cmd);
+                }
+            },
+            _ => {}
         }
 
-        self.text_area.handle_letter_event(event)
+        window_cmd
+    }
+}
+
+fn history_message(verb: &str, result: Result<Option<String>>) -> String {
+    match result {
+        Ok(Some(operation)) => format!("{verb}: {operation}"),
+        Ok(None) => format!("nothing to be {verb}"),
+        Err(err) if err.is::<Conflict>() => format!("not {verb}, {err}"),
+        Err(err) => {
+            error!("couldn't apply history: {err}");
+            err.to_string()
+        }
     }
 }
 
 enum _WindowCommand {
     Quit,
     SwitchMode(LetterMode),
+    OpenWindow(Box<dyn Window>),
 }
 
 type WindowCommand = Option<_WindowCommand>;
@@ -133,7 +550,8 @@ struct WindowManager {
     state: LetterState,
 
     keycommand_composer: KeyCommandComposer<LetterCommand>,
-    letter_command_receiver: Receiver<LetterCommand>
+    letter_command_receiver: Receiver<LetterCommand>,
+    last_reload_check: Instant
 }
 
 impl WindowManager {
@@ -156,11 +574,35 @@ impl WindowManager {
         keycommand_composer.register_keycommand(vec![KeyCode::Char(' '), KeyCode::Char('q')], LetterCommand::Quit);
         keycommand_composer.register_keycommand(vec![KeyCode::Char('d'), KeyCode::Char('d')], LetterCommand::Delete(DeleteCommand::DeleteLine));
         keycommand_composer.register_keycommand(vec![KeyCode::Char('x')], LetterCommand::Delete(DeleteCommand::DeleteChar));
+        keycommand_composer.register_keycommand(vec![KeyCode::Char('>'), KeyCode::Char('>')], LetterCommand::Tree(TreeCommand::Indent));
+        keycommand_composer.register_keycommand(vec![KeyCode::Char('<'), KeyCode::Char('<')], LetterCommand::Tree(TreeCommand::Outdent));
+        keycommand_composer.register_keycommand(vec![KeyCode::Char('z'), KeyCode::Char('a')], LetterCommand::Tree(TreeCommand::ToggleFold));
+        keycommand_composer.register_keycommand(vec![KeyCode::Char('z'), KeyCode::Char('o')], LetterCommand::Tree(TreeCommand::OpenFold));
+        keycommand_composer.register_keycommand(vec![KeyCode::Char('z'), KeyCode::Char('c')], LetterCommand::Tree(TreeCommand::CloseFold));
+        keycommand_composer.register_keycommand(vec![KeyCode::Char('z'), KeyCode::Char('R')], LetterCommand::Tree(TreeCommand::OpenAllFolds));
+        keycommand_composer.register_keycommand(vec![KeyCode::Char('z'), KeyCode::Char('M')], LetterCommand::Tree(TreeCommand::CloseAllFolds));
+        keycommand_composer.register_keycommand(vec![KeyCode::Char(' '), KeyCode::Char('d')], LetterCommand::EditDate(DateField::Due));
+        keycommand_composer.register_keycommand(vec![KeyCode::Char(' '), KeyCode::Char('s')], LetterCommand::EditDate(DateField::Start));
+        keycommand_composer.register_keycommand(vec![KeyCode::Char(' '), KeyCode::Char('l')], LetterCommand::PickList);
+        keycommand_composer.register_keycommand(vec![KeyCode::Char(' '), KeyCode::Char('b')], LetterCommand::ManageBadges);
+        keycommand_composer.register_keycommand(vec![KeyCode::Char(' '), KeyCode::Char('t')], LetterCommand::OpenTrash);
+        keycommand_composer.register_keycommand(vec![KeyCode::Char(' '), KeyCode::Char('i')], LetterCommand::ShowDetails);
+        keycommand_composer.register_keycommand(vec![KeyCode::Char(' '), KeyCode::Char('o')], LetterCommand::SortTasks);
+        keycommand_composer.register_keycommand(vec![KeyCode::Char(' '), KeyCode::Char('m')], LetterCommand::MoveTaskToList);
+        keycommand_composer.register_keycommand(vec![KeyCode::Char(' '), KeyCode::Char('r')], LetterCommand::EditRecurrence);
+        keycommand_composer.register_keycommand(vec![KeyCode::Char('t')], LetterCommand::SelectBadge);
+        keycommand_composer.register_keycommand(vec![KeyCode::Char('T')], LetterCommand::CycleBadge);
+        keycommand_composer.register_keycommand(vec![KeyCode::Char(' '), KeyCode::Char('c')], LetterCommand::StartTimer);
+        keycommand_composer.register_keycommand(vec![KeyCode::Char(' '), KeyCode::Char('C')], LetterCommand::StopTimer);
+        keycommand_composer.register_keycommand(vec![KeyCode::Char(' '), KeyCode::Char('k')], LetterCommand::ShowDependencies);
+        keycommand_composer.register_keycommand(vec![KeyCode::Char('u')], LetterCommand::Undo);
+        keycommand_composer.register_keycommand(vec![KeyCode::Char('/')], LetterCommand::Search);
+        keycommand_composer.register_ctrl_keycommand(KeyCode::Char('r'), LetterCommand::Redo);
 
-        WindowManager { windows, terminal, state, keycommand_composer, letter_command_receiver: rx }
+        WindowManager { windows, terminal, state, keycommand_composer, letter_command_receiver: rx, last_reload_check: Instant::now() }
     }
 
-    fn handle_window_command(&mut self, window_idx: usize, cmd: &WindowCommand) {
+    fn handle_window_command(&mut self, window_idx: usize, cmd: WindowCommand) {
         if let Some(cmd) = cmd {
             match cmd {
                 _WindowCommand::Quit => {
@@ -171,12 +613,36 @@ impl WindowManager {
                 },
                 _WindowCommand::SwitchMode(mode) => {
                     self.keycommand_composer.clear_composition();
-                    self.state.mode = *mode;
+                    self.state.mode = mode;
+                },
+                _WindowCommand::OpenWindow(window) => {
+                    self.keycommand_composer.clear_composition();
+                    self.push_window(window);
                 }
             }
         }
     }
 
+    // picks up changes made by other processes, but not while a line is being edited or a
+    // popup holds on to tasks of the old state
+    fn reload_if_changed(&mut self) {
+        if self.last_reload_check.elapsed() < RELOAD_INTERVAL {
+            return;
+        }
+        self.last_reload_check = Instant::now();
+
+        let popup_open = self.windows.iter().any(|window| window.is_popup());
+        if popup_open || matches!(self.state.mode, LetterMode::Insert) {
+            return;
+        }
+
+        match self.state.store.reload_if_changed() {
+            Ok(true) => self.state.message = Some("reloaded, the database was changed elsewhere".to_string()),
+            Ok(false) => {},
+            Err(err) => error!("couldn't look for changes: {err}")
+        }
+    }
+
     fn run(&mut self) -> Result<()> {
         enable_raw_mode()?;
         self.terminal.clear()?;
@@ -193,25 +659,25 @@ impl WindowManager {
         });
 
         loop {
-            let cmds: Vec<(usize, WindowCommand)> = self.windows.iter_mut()
-                .enumerate()
-                .map(|(idx, window)| (idx, window.update(&mut self.state)))
-                .collect();
-
-            cmds.iter().for_each(|(window_idx, cmd)| {
-                self.handle_window_command(*window_idx, cmd)
-            });
-
             if let Ok(key_event) = rx.try_recv() {
+                self.state.message = None;
+                let last_idx = self.windows.len() - 1;
+                let popup_focused = self.windows[last_idx].is_popup();
                 match self.state.mode {
+                    _ if popup_focused => {
+                        let cmd = self.windows.get_mut(last_idx).unwrap()
+                            .handle_event(&mut self.state, LetterEvent::RawKeyInputEvent(key_event.code));
+
+                        self.handle_window_command(last_idx, cmd);
+                    },
                     LetterMode::Normal => {
-                        self.keycommand_composer.push_key(key_event.code);
+                        self.keycommand_composer.push_key_event(key_event);
                         if let Ok(cmd) = self.letter_command_receiver.try_recv() {
                             let last_idx = self.windows.len() - 1;
                             let wcmd = self.windows.get_mut(last_idx).unwrap()
                                 .handle_event(&mut self.state, LetterEvent::CommandEvent(cmd));
 
-                            self.handle_window_command(last_idx, &wcmd);
+                            self.handle_window_command(last_idx, wcmd);
                         }
                     },
                     LetterMode::Insert => {
@@ -219,15 +685,29 @@ impl WindowManager {
                         let cmd = self.windows.get_mut(last_idx).unwrap()
                             .handle_event(&mut self.state, LetterEvent::RawKeyInputEvent(key_event.code));
 
-                        self.handle_window_command(last_idx, &cmd);
+                        self.handle_window_command(last_idx, cmd);
                     }
                 }
             }
 
+            self.reload_if_changed();
+
+            let cmds: Vec<(usize, WindowCommand)> = self.windows.iter_mut()
+                .enumerate()
+                .map(|(idx, window)| (idx, window.update(&mut self.state)))
+                .collect();
+
+            cmds.into_iter().for_each(|(window_idx, cmd)| {
+                self.handle_window_command(window_idx, cmd)
+            });
+
             self.terminal.draw(|frame| {
-                let percentage_per_window = 100 / self.windows.len() as u16;
+                let (popups, windows): (Vec<_>, Vec<_>) = self.windows.iter()
+                    .partition(|window| window.is_popup());
 
-                let constraints: Vec<Constraint> = self.windows.iter()
+                let percentage_per_window = 100 / windows.len() as u16;
+
+                let constraints: Vec<Constraint> = windows.iter()
                     .map(|_| Constraint::Percentage(percentage_per_window))
                     .collect();
 
@@ -244,12 +724,15 @@ impl WindowManager {
                     .constraints(constraints)
                     .split(panel_grid[0]);
 
-                self.windows.iter()
+                windows.iter()
                     .enumerate()
                     .for_each(|(idx, window)| {
                         window.draw(&self.state, frame, window_grid[idx]);
                     });
 
+                popups.iter()
+                    .for_each(|popup| popup.draw(&self.state, frame, panel_grid[0]));
+
                 Self::draw_status_bar(&self.state, frame, panel_grid[1]);
             })?;
         }
@@ -257,8 +740,25 @@ impl WindowManager {
     }
 
     fn draw_status_bar(state: &LetterState, frame: &mut Frame, rect: Rect) {
-        let status_paragraph = Paragraph::new(format!("-- {} --", state.mode));
+        let message = state.message.as_deref().unwrap_or_default();
+        let status_paragraph = Paragraph::new(format!("-- {} --  {message}", state.mode));
         frame.render_widget(status_paragraph, rect);
+
+        // the running timer goes in front of the location
+        let timer_span = state.store.running_timer().map(|entry| {
+            let task_text = state.store.tasks.iter()
+                .find(|task| task.id == Some(entry.task_id))
+                .map(|task| task.text.as_str())
+                .unwrap_or("task of another list");
+            let clock = dates::format_clock(entry.duration(dates::now()));
+
+            Span::styled(format!("⏱ {clock} {task_text}  "), Style::default().fg(Color::LightGreen))
+        });
+
+        let location_span = Span::styled(state.store.location(), Style::default().fg(Color::DarkGray));
+        let location_paragraph = Paragraph::new(Line::from(timer_span.into_iter().chain([location_span]).collect::<Vec<_>>()))
+            .alignment(Alignment::Right);
+        frame.render_widget(location_paragraph, rect);
     }
 
     fn push_window(&mut self, window: Box<dyn Window>) {
@@ -266,16 +766,35 @@ impl WindowManager {
     }
 }
 
-fn create_database_connection() -> Result<Connection> {
-    let db_path_str = "./.letter.db";
-
-    let db_path = PathBuf::from(db_path_str);
+fn create_database_connection(db_path: &Path) -> Result<Connection> {
     if !db_path.exists() {
         File::create(db_path)?;
     }
 
-    Connection::open(db_path_str)
-        .map_err(|_| "cannot open sqlite database file".into())
+    let connection = Connection::open(db_path)
+        .map_err(|_| "cannot open sqlite database file")?;
+
+    // other processes may read while one writes, and a write waits for the other writer
+    // to finish instead of failing right away
+    connection.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+    connection.busy_timeout(BUSY_TIMEOUT)?;
+
+    Ok(connection)
+}
+
+// a Markdown file is edited in place, nothing is written to disk with --in-memory and
+// everything else is a SQLite database
+fn create_storage(args: &Args) -> Result<Box<dyn Storage>> {
+    if args.in_memory {
+        return Ok(Box::new(MemoryStorage::new()));
+    }
+
+    let db_path = args.database_path()?;
+    if db_path.extension().is_some_and(|extension| extension == "md") {
+        return Ok(Box::new(MarkdownStorage::open(&db_path)?));
+    }
+
+    Ok(Box::new(SqliteStorage::open(create_database_connection(&db_path)?)?))
 }
 
 #[derive(Clone, Copy)]
@@ -294,10 +813,38 @@ enum DeleteCommand {
     DeleteChar,
 }
 
+#[derive(Clone, Copy)]
+enum TreeCommand {
+    Indent,
+    Outdent,
+    ToggleFold,
+    OpenFold,
+    CloseFold,
+    OpenAllFolds,
+    CloseAllFolds,
+}
+
 #[derive(Clone, Copy)]
 enum LetterCommand {
     MoveCursor(CursorDirection),
     Delete(DeleteCommand),
+    Tree(TreeCommand),
+    EditDate(DateField),
+    PickList,
+    MoveTaskToList,
+    Undo,
+    Redo,
+    Search,
+    ManageBadges,
+    OpenTrash,
+    ShowDetails,
+    SortTasks,
+    SelectBadge,
+    EditRecurrence,
+    StartTimer,
+    StopTimer,
+    ShowDependencies,
+    CycleBadge,
     Quit,
     SwitchMode(LetterMode),
 }
@@ -308,12 +855,156 @@ enum LetterEvent {
     RawKeyInputEvent(KeyCode)
 }
 
-fn main() -> Result<()> {
-    let connection = create_database_connection()?;
+// the SQLite database itself, for what goes past the task store, a missing one is only created
+// when asked to
+fn open_database(args: &Args, action: &str, create: bool) -> Result<Connection> {
+    let db_path = args.database_path()?;
+    if args.in_memory || db_path.extension().is_some_and(|extension| extension == "md") {
+        return Err(format!("{action} only works with SQLite databases").into());
+    }
+    if !create && !db_path.exists() {
+        return Err(format!("{} doesn't exist", cli::display_path(&db_path)).into());
+    }
 
-    let mut task_store = TaskStore::new(connection);
+    create_database_connection(&db_path)
+}
+
+// repairs unless it's a dry run, fails if problems are left
+fn run_fsck(args: &Args, dry_run: bool) -> Result<()> {
+    let db_path = args.database_path()?;
+    let problems = store::check_database(&mut open_database(args, "fsck", false)?, dry_run)?;
+    for problem in &problems {
+        match (problem.repair, dry_run) {
+            (Some(repair), false) => println!("{}: {repair}", problem.description),
+            (Some(_), true) | (None, _) => println!("{}", problem.description)
+        }
+    }
+
+    let unrepaired = problems.iter().filter(|problem| problem.repair.is_none()).count();
+    match (problems.len(), unrepaired) {
+        (0, _) => println!("no problems found in {}", cli::display_path(&db_path)),
+        (found, _) if dry_run => return Err(format!("found {found} problems").into()),
+        (found, 0) => println!("repaired {found} problems"),
+        (found, _) => return Err(format!("{unrepaired} of {found} problems can't be repaired by fsck").into())
+    }
+
+    Ok(())
+}
+
+// loaded and on the list asked for with --list
+fn open_store(args: &Args) -> Result<TaskStore> {
+    let mut task_store = TaskStore::new(create_storage(args)?);
     task_store.fetch_data()?;
 
+    if let Some(name) = &args.list {
+        let list_id = task_store.lists.iter()
+            .find(|list| list.name == *name)
+            .map(|list| list.id)
+            .ok_or_else(|| format!("there's no list named {name}"))?;
+        task_store.switch_list(list_id)?;
+    }
+
+    Ok(task_store)
+}
+
+// the format asked for or else the one the file looks like
+fn transfer_format(format: Option<Format>, path: Option<&Path>) -> Result<Format> {
+    format.or_else(|| path.and_then(Format::from_path))
+        .ok_or_else(|| "the format can't be guessed, pass --format".into())
+}
+
+fn run_export(args: &Args, format: Option<Format>, output: Option<&Path>) -> Result<()> {
+    let content = match transfer_format(format, output)? {
+        Format::Markdown => transfer::markdown::export(&open_store(args)?),
+        Format::TodoTxt => transfer::todotxt::export(&open_store(args)?),
+        Format::Ical => transfer::ical::export(&open_store(args)?),
+        Format::Taskwarrior => return Err("Taskwarrior exports can only be imported".into()),
+        Format::Json => store::export_database(&mut open_database(args, "the JSON export", false)?)?
+    };
+
+    match output {
+        Some(path) => fs::write(path, content)?,
+        None => print!("{content}")
+    }
+
+    Ok(())
+}
+
+fn run_import(args: &Args, format: Option<Format>, input: Option<&Path>, replace: bool, dry_run: bool, order: Option<TaskOrder>) -> Result<()> {
+    let input = input.filter(|path| *path != Path::new("-"));
+    let content = match input {
+        Some(path) => fs::read_to_string(path)?,
+        None => {
+            let mut content = String::new();
+            io::stdin().read_to_string(&mut content)?;
+            content
+        }
+    };
+
+    // Taskwarrior exports are saved as `.json` too, they are an array where a dump is an object
+    let guessed = format.is_none();
+    let format = match transfer_format(format, input)? {
+        Format::Json if guessed && matches!(serde_json::from_str(&content), Ok(serde_json::Value::Array(_))) => Format::Taskwarrior,
+        format => format
+    };
+    if order.is_some() && !matches!(format, Format::Taskwarrior) {
+        return Err("--order only works with Taskwarrior exports".into());
+    }
+
+    // the report tells what couldn't be imported
+    let (mut task_store, tasks, report) = match (format, replace, dry_run) {
+        (Format::Json, true, _) => return Err("--replace doesn't work with JSON, the dump updates rows by id".into()),
+        (Format::Json, false, _) => return run_dump_import(args, &content, dry_run),
+        (_, _, true) => return Err("--dry-run only works with JSON".into()),
+        (Format::Markdown, _, _) => (open_store(args)?, transfer::markdown::import(&content), vec![]),
+        (Format::TodoTxt, _, _) => (open_store(args)?, transfer::todotxt::import(&content), vec![]),
+        (Format::Ical, _, _) => (open_store(args)?, transfer::ical::import(&content), vec![]),
+        (Format::Taskwarrior, _, _) => {
+            // tags are matched against the badges there are
+            let task_store = open_store(args)?;
+            let (tasks, report) = transfer::taskwarrior::import(&content, &task_store, order.unwrap_or_default())?;
+            (task_store, tasks, report)
+        }
+    };
+
+    let mode = if replace { ImportMode::Replace } else { ImportMode::Append };
+    let count = task_store.import_tasks(tasks, mode)?;
+
+    let list_name = task_store.current_list().map(|list| list.name.clone()).unwrap_or_default();
+    println!("imported {count} tasks into {list_name}");
+    for line in report {
+        println!("{line}");
+    }
+
+    Ok(())
+}
+
+fn run_dump_import(args: &Args, content: &str, dry_run: bool) -> Result<()> {
+    let changes = store::import_database(&mut open_database(args, "the JSON import", true)?, content, dry_run)?;
+    for change in &changes {
+        println!("{change}");
+    }
+
+    match (changes.len(), dry_run) {
+        (0, _) => println!("no changes"),
+        (count, true) => println!("{count} changes, nothing written (dry run)"),
+        (count, false) => println!("{count} changes applied")
+    }
+
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse()?;
+    match &args.command {
+        Some(Command::Fsck { dry_run }) => return run_fsck(&args, *dry_run),
+        Some(Command::Export { format, output }) => return run_export(&args, *format, output.as_deref()),
+        Some(Command::Import { format, input, replace, dry_run, order }) => return run_import(&args, *format, input.as_deref(), *replace, *dry_run, *order),
+        None => {}
+    }
+
+    let task_store = open_store(&args)?;
+
     let mut window_manager = WindowManager::new(task_store);
     let task_list_window = TaskListWindow::new(&window_manager.state.store);
     window_manager.push_window(Box::new(task_list_window));
@@ -0,0 +1,157 @@
+use rusqlite::Connection;
+use serde_json::{Map, Value};
+
+use super::{history, migrations, Result};
+
+// Version of the JSON dump, it changes whenever a table or column below is added, renamed or
+// dropped or the meaning of a value changes. Dumps of another version are refused.
+pub const DUMP_VERSION: i64 = 1;
+
+// A dump is a single object holding `"version": 1` and one array per table, rows are objects
+// with these fields, ordered by id:
+//
+// - lists: id, name and sort_order
+// - badges: id, name, color as `#RRGGBB`, sort_order and done, 1 for badges that complete a task
+// - badge_transitions: id, from_id and to_id, the badges a task may go from and to
+// - notes: id, text, created_at and updated_at
+// - tasks: id, list_id, parent_id, sort_order, text, badge_id, note_id, due_date, start_date,
+//   recurrence like `weekly:mon,thu`, created_at, updated_at, completed_at and deleted_at,
+//   which is set for tasks in the trash
+// - time_entries: id, task_id, started_at and stopped_at, which is null while the timer runs
+// - task_dependencies: id, task_id and blocker_id, the task waits for the blocker
+//
+// Dates look like `2024-05-01`, timestamps like `2024-05-01 09:30:00` and fields without a value
+// are null. Tables are optional when importing, tables and rows left out stay as they are.
+// Each table comes with what one of its rows is called in the diff.
+const TABLES: &[(&str, &str, &[&str])] = &[
+    ("lists", "list", &["id", "name", "sort_order"]),
+    ("badges", "badge", &["id", "name", "color", "sort_order", "done"]),
+    ("badge_transitions", "badge transition", &["id", "from_id", "to_id"]),
+    ("notes", "note", &["id", "text", "created_at", "updated_at"]),
+    ("tasks", "task", &["id", "list_id", "parent_id", "sort_order", "text", "badge_id", "note_id", "due_date", "start_date", "recurrence", "created_at", "updated_at", "completed_at", "deleted_at"]),
+    ("time_entries", "time entry", &["id", "task_id", "started_at", "stopped_at"]),
+    ("task_dependencies", "dependency", &["id", "task_id", "blocker_id"])
+];
+
+// like `task 3 "call mom"`, rows with a name or text show it
+fn row_name(kind: &str, row: &Map<String, Value>) -> String {
+    let id = row.get("id").cloned().unwrap_or(Value::Null);
+    let label = row.get("name").or_else(|| row.get("text")).filter(|_| kind != "note");
+
+    match label.and_then(Value::as_str) {
+        Some(label) => format!("{kind} {id} \"{label}\""),
+        None => format!("{kind} {id}")
+    }
+}
+
+fn json_object_sql(columns: &[&str]) -> String {
+    let fields: Vec<String> = columns.iter().map(|column| format!("'{column}', {column}")).collect();
+    format!("json_object({})", fields.join(", "))
+}
+
+fn load_row(connection: &Connection, table: &str, columns: &[&str], id: i64) -> Result<Option<Map<String, Value>>> {
+    let row: Option<String> = connection.prepare(&format!("SELECT {} FROM {table} WHERE id = ?1", json_object_sql(columns)))?
+        .query_map((id,), |row| row.get(0))?
+        .next()
+        .transpose()?;
+
+    match row.map(|row| serde_json::from_str(&row)) {
+        Some(Ok(Value::Object(row))) => Ok(Some(row)),
+        Some(Ok(_)) => Err(format!("{table} row {id} isn't an object").into()),
+        Some(Err(err)) => Err(err.into()),
+        None => Ok(None)
+    }
+}
+
+// every table as pretty printed JSON
+pub fn export_database(connection: &mut Connection) -> Result<String> {
+    migrations::migrate(connection)?;
+
+    let mut dump = Map::new();
+    dump.insert("version".to_string(), Value::from(DUMP_VERSION));
+    for (table, _, columns) in TABLES {
+        let rows = connection.prepare(&format!("SELECT {} FROM {table} ORDER BY id", json_object_sql(columns)))?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .map(|row| Ok(serde_json::from_str::<Value>(&row?)?))
+            .collect::<Result<Vec<Value>>>()?;
+        dump.insert(table.to_string(), Value::Array(rows));
+    }
+
+    Ok(serde_json::to_string_pretty(&Value::Object(dump))? + "\n")
+}
+
+// Writes every row of the dump, a row with the id of an existing one replaces it. Importing
+// the same dump again changes nothing. Returns the differences, a dry run only looks for them.
+// The import is a single operation in the undo history.
+pub fn import_database(connection: &mut Connection, content: &str, dry_run: bool) -> Result<Vec<String>> {
+    let dump: Value = serde_json::from_str(content)?;
+    let version = dump.get("version").and_then(Value::as_i64).ok_or("that's not a letter dump, it has no version. Taskwarrior exports are imported with --format tw")?;
+    if version != DUMP_VERSION {
+        return Err(format!("the dump has version {version} but this letter reads version {DUMP_VERSION}").into());
+    }
+
+    migrations::migrate(connection)?;
+    history::install(connection)?;
+    connection.pragma_update(None, "foreign_keys", true)?;
+
+    let transaction = connection.transaction()?;
+    // rows may refer to rows that come later in the dump
+    transaction.pragma_update(None, "defer_foreign_keys", true)?;
+    history::begin_operation(&transaction, "import dump")?;
+
+    let mut changes = vec![];
+    for (table, kind, columns) in TABLES {
+        let Some(rows) = dump.get(table) else {
+            continue
+        };
+        let rows = rows.as_array().ok_or_else(|| format!("{table} isn't an array"))?;
+
+        for row in rows {
+            let row = row.as_object().ok_or_else(|| format!("a row of {table} isn't an object"))?;
+            let id = row.get("id").and_then(Value::as_i64).ok_or_else(|| format!("a row of {table} has no id"))?;
+            if let Some(column) = columns.iter().find(|column| !row.contains_key(**column)) {
+                return Err(format!("{} has no {column}", row_name(kind, row)).into());
+            }
+
+            match load_row(&transaction, table, columns, id)? {
+                None => changes.push(format!("+ {}", row_name(kind, row))),
+                Some(existing) => {
+                    let changed: Vec<String> = columns.iter()
+                        .filter(|column| existing.get(**column) != row.get(**column))
+                        .map(|column| format!("{column} {} → {}", existing[*column], row[*column]))
+                        .collect();
+                    if changed.is_empty() {
+                        continue;
+                    }
+                    changes.push(format!("~ {}: {}", row_name(kind, &existing), changed.join(", ")));
+                }
+            }
+
+            // same upsert as undo uses, revisions go up so other processes notice the change
+            let values: Vec<String> = columns.iter().map(|column| format!("json_extract(?1, '$.{column}')")).collect();
+            let mut assignments: Vec<String> = columns.iter().skip(1).map(|column| format!("{column} = excluded.{column}")).collect();
+            if matches!(*table, "notes" | "tasks") {
+                assignments.push("revision = revision + 1".to_string());
+            }
+            transaction.execute(&format!(r#"
+                INSERT INTO {table} ({}) SELECT {} WHERE true
+                    ON CONFLICT (id) DO UPDATE SET {}
+            "#, columns.join(", "), values.join(", "), assignments.join(", ")), (Value::Object(row.clone()).to_string(),))?;
+        }
+    }
+
+    let violation: Option<(String, i64, String)> = transaction.prepare("PRAGMA foreign_key_check")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .next()
+        .transpose()?;
+    if let Some((table, row_id, parent)) = violation {
+        return Err(format!("row {row_id} of {table} would refer to a row of {parent} that doesn't exist").into());
+    }
+
+    match dry_run {
+        true => transaction.rollback()?,
+        false => transaction.commit()?
+    }
+
+    Ok(changes)
+}
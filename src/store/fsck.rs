@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+
+use rusqlite::{Connection, Transaction};
+
+use super::{migrations, Result, SORT_ORDER_STEP};
+
+// Something wrong in the database, described the way it's shown to the user.
+pub struct Problem {
+    pub description: String,
+    // what the repair does about it, None if it has to be fixed by hand
+    pub repair: Option<&'static str>
+}
+
+// references that lead nowhere: a query describing each broken row and the statement that
+// repairs all of them, the order matters since later checks rely on the earlier repairs
+const CHECKS: &[(&str, &str, &str)] = &[
+    (r#"
+        SELECT 'task ' || id || ' "' || text || '" belongs to list ' || coalesce(list_id, 'NULL') || ', which doesn''t exist' FROM tasks
+        WHERE list_id IS NULL OR list_id NOT IN (SELECT id FROM lists)
+    "#, r#"
+        UPDATE tasks SET list_id = (SELECT id FROM lists ORDER BY sort_order LIMIT 1)
+        WHERE list_id IS NULL OR list_id NOT IN (SELECT id FROM lists)
+    "#, "moved to the first list"),
+
+    (r#"
+        SELECT 'task ' || id || ' "' || text || '" has badge ' || badge_id || ', which doesn''t exist' FROM tasks
+        WHERE badge_id IS NOT NULL AND badge_id NOT IN (SELECT id FROM badges)
+    "#, r#"
+        UPDATE tasks SET badge_id = NULL, completed_at = NULL
+        WHERE badge_id IS NOT NULL AND badge_id NOT IN (SELECT id FROM badges)
+    "#, "badge removed"),
+
+    (r#"
+        SELECT 'task ' || id || ' "' || text || '" has note ' || note_id || ', which doesn''t exist' FROM tasks
+        WHERE note_id IS NOT NULL AND note_id NOT IN (SELECT id FROM notes)
+    "#, r#"
+        UPDATE tasks SET note_id = NULL
+        WHERE note_id IS NOT NULL AND note_id NOT IN (SELECT id FROM notes)
+    "#, "note removed"),
+
+    (r#"
+        SELECT 'task ' || id || ' "' || text || '" has parent ' || parent_id || ', which isn''t in its list' FROM tasks
+        WHERE parent_id IS NOT NULL AND parent_id NOT IN (SELECT id FROM tasks AS parent WHERE parent.list_id = tasks.list_id)
+    "#, r#"
+        UPDATE tasks SET parent_id = NULL
+        WHERE parent_id IS NOT NULL AND parent_id NOT IN (SELECT id FROM tasks AS parent WHERE parent.list_id = tasks.list_id)
+    "#, "moved to the top level"),
+
+    (r#"
+        SELECT 'note ' || id || ' belongs to no task' FROM notes
+        WHERE id NOT IN (SELECT note_id FROM tasks WHERE note_id IS NOT NULL)
+    "#, r#"
+        DELETE FROM notes
+        WHERE id NOT IN (SELECT note_id FROM tasks WHERE note_id IS NOT NULL)
+    "#, "deleted"),
+
+    (r#"
+        SELECT 'time entry ' || id || ' belongs to task ' || task_id || ', which doesn''t exist' FROM time_entries
+        WHERE task_id NOT IN (SELECT id FROM tasks)
+    "#, r#"
+        DELETE FROM time_entries
+        WHERE task_id NOT IN (SELECT id FROM tasks)
+    "#, "deleted"),
+
+    (r#"
+        SELECT 'dependency ' || id || ' links task ' || task_id || ' and ' || blocker_id || ', one of them doesn''t exist' FROM task_dependencies
+        WHERE task_id NOT IN (SELECT id FROM tasks) OR blocker_id NOT IN (SELECT id FROM tasks)
+    "#, r#"
+        DELETE FROM task_dependencies
+        WHERE task_id NOT IN (SELECT id FROM tasks) OR blocker_id NOT IN (SELECT id FROM tasks)
+    "#, "deleted"),
+
+    (r#"
+        SELECT 'badge transition ' || id || ' links badge ' || from_id || ' and ' || to_id || ', one of them doesn''t exist' FROM badge_transitions
+        WHERE from_id NOT IN (SELECT id FROM badges) OR to_id NOT IN (SELECT id FROM badges)
+    "#, r#"
+        DELETE FROM badge_transitions
+        WHERE from_id NOT IN (SELECT id FROM badges) OR to_id NOT IN (SELECT id FROM badges)
+    "#, "deleted"),
+];
+
+// Looks for rows referring to rows that don't exist and lists whose order is broken, and
+// repairs them unless it's a dry run. Everything happens in one transaction and the undo
+// history doesn't know about the repairs.
+pub fn check_database(connection: &mut Connection, dry_run: bool) -> Result<Vec<Problem>> {
+    migrations::migrate(connection)?;
+
+    let transaction = connection.transaction()?;
+    let mut problems = vec![];
+
+    for (query, repair_statement, repair) in CHECKS {
+        let descriptions: Vec<String> = transaction.prepare(query)?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        if !descriptions.is_empty() {
+            transaction.execute(repair_statement, ())?;
+        }
+
+        problems.extend(descriptions.into_iter().map(|description| Problem { description, repair: Some(repair) }));
+    }
+
+    problems.extend(check_order(&transaction)?);
+
+    // whatever the checks above don't know about
+    let violations: Vec<(String, i64, String)> = transaction.prepare("PRAGMA foreign_key_check")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    problems.extend(violations.into_iter().map(|(table, row_id, parent)| Problem {
+        description: format!("row {row_id} of {table} refers to a row of {parent} that doesn't exist"),
+        repair: None
+    }));
+
+    match dry_run {
+        true => transaction.rollback()?,
+        false => transaction.commit()?
+    }
+
+    Ok(problems)
+}
+
+// a list is in order if no two tasks share a sort order and every subtask comes after its
+// parent, broken lists are spread out again in tree order
+fn check_order(transaction: &Transaction) -> Result<Vec<Problem>> {
+    let lists: Vec<(i64, String)> = transaction.prepare("SELECT id, name FROM lists ORDER BY sort_order")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let mut problems = vec![];
+    for (list_id, name) in lists {
+        let tasks: Vec<(i64, Option<i64>, i64)> = transaction.prepare(r#"
+            SELECT id, parent_id, sort_order FROM tasks
+            WHERE list_id = ?1 AND deleted_at IS NULL
+            ORDER BY sort_order, id
+        "#)?
+            .query_map((list_id,), |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let shared = tasks.windows(2).filter(|pair| pair[0].2 == pair[1].2).count();
+        let order = tree_order(&tasks);
+        let misplaced = order.iter().zip(&tasks).filter(|(id, task)| **id != task.0).count();
+
+        let description = match (shared, misplaced) {
+            (0, 0) => continue,
+            (0, _) => format!("list \"{name}\" has subtasks above their parent"),
+            (_, _) => format!("list \"{name}\" has tasks sharing a sort order")
+        };
+
+        for (idx, task_id) in order.iter().enumerate() {
+            transaction.execute("UPDATE tasks SET sort_order = ?1 WHERE id = ?2", (idx as i64 * SORT_ORDER_STEP, task_id))?;
+        }
+        problems.push(Problem { description, repair: Some("renumbered") });
+    }
+
+    Ok(problems)
+}
+
+// task ids with every subtree right below its root, siblings keep their order
+fn tree_order(tasks: &[(i64, Option<i64>, i64)]) -> Vec<i64> {
+    let mut children: HashMap<Option<i64>, Vec<i64>> = HashMap::new();
+    for (id, parent_id, _) in tasks {
+        let parent_id = parent_id.filter(|parent_id| tasks.iter().any(|task| task.0 == *parent_id));
+        children.entry(parent_id).or_default().push(*id);
+    }
+
+    let mut order = Vec::with_capacity(tasks.len());
+    let mut pending: Vec<i64> = children.get(&None).cloned().unwrap_or_default();
+    pending.reverse();
+    while let Some(id) = pending.pop() {
+        order.push(id);
+        if let Some(subtasks) = children.get(&Some(id)) {
+            pending.extend(subtasks.iter().rev());
+        }
+    }
+
+    // tasks in a parent cycle are never reached from the top level
+    order.extend(tasks.iter().map(|task| task.0).filter(|id| !order.contains(id)).collect::<Vec<_>>());
+    order
+}
@@ -0,0 +1,288 @@
+use std::collections::HashSet;
+
+use rusqlite::{Connection, OptionalExtension};
+
+use super::{Conflict, Result};
+
+// rows of these tables can be restored by undo and redo
+const TRACKED_TABLES: &[&str] = &["lists", "badges", "notes", "tasks", "time_entries", "task_dependencies", "badge_transitions"];
+
+// only the most recent operations are kept around
+const MAX_OPERATIONS: i64 = 1000;
+
+fn table_columns(connection: &Connection, table: &str) -> Result<Vec<String>> {
+    let columns = connection.prepare(&format!("PRAGMA table_info({table})"))?
+        .query_map([], |row| row.get::<&str, String>("name"))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    Ok(columns)
+}
+
+// Changes are captured by temporary triggers, they only exist for this connection so
+// writes of other processes never end up in our history. The triggers are generated
+// from the current columns and need to be installed again after every migration.
+// Operations are tagged with a session made up for the connection, several processes share
+// the history table and each one only undoes its own operations.
+pub fn install(connection: &Connection) -> Result<()> {
+    connection.execute_batch(r#"
+        CREATE TEMP TABLE IF NOT EXISTS history_state (
+            operation_id INTEGER,
+            session      TEXT NOT NULL
+        );
+
+        INSERT INTO temp.history_state (operation_id, session)
+            SELECT NULL, lower(hex(randomblob(8))) WHERE NOT EXISTS (SELECT 1 FROM temp.history_state);
+        UPDATE temp.history_state SET operation_id = NULL;
+    "#)?;
+
+    for table in TRACKED_TABLES {
+        let columns = table_columns(connection, table)?;
+        let row_json = |row: &str| {
+            let fields: Vec<String> = columns.iter()
+                .map(|column| format!("'{column}', {row}.{column}"))
+                .collect();
+            format!("json_object({})", fields.join(", "))
+        };
+
+        let recording = "(SELECT operation_id FROM temp.history_state)";
+        connection.execute_batch(&format!(r#"
+            DROP TRIGGER IF EXISTS temp.history_{table}_insert;
+            DROP TRIGGER IF EXISTS temp.history_{table}_update;
+            DROP TRIGGER IF EXISTS temp.history_{table}_delete;
+
+            CREATE TEMP TRIGGER history_{table}_insert AFTER INSERT ON main.{table}
+            WHEN {recording} IS NOT NULL
+            BEGIN
+                INSERT INTO history_changes (operation_id, table_name, row_id, before, after)
+                    VALUES ({recording}, '{table}', NEW.id, NULL, {new});
+            END;
+
+            CREATE TEMP TRIGGER history_{table}_update AFTER UPDATE ON main.{table}
+            WHEN {recording} IS NOT NULL
+            BEGIN
+                INSERT INTO history_changes (operation_id, table_name, row_id, before, after)
+                    VALUES ({recording}, '{table}', NEW.id, {old}, {new});
+            END;
+
+            CREATE TEMP TRIGGER history_{table}_delete AFTER DELETE ON main.{table}
+            WHEN {recording} IS NOT NULL
+            BEGIN
+                INSERT INTO history_changes (operation_id, table_name, row_id, before, after)
+                    VALUES ({recording}, '{table}', OLD.id, {old}, NULL);
+            END;
+        "#, old = row_json("OLD"), new = row_json("NEW")))?;
+    }
+
+    Ok(())
+}
+
+const SESSION: &str = "(SELECT session FROM temp.history_state)";
+
+// every change from now on belongs to a new operation, operations of this session that were
+// undone can't be redone anymore afterwards
+pub fn begin_operation(connection: &Connection, name: &str) -> Result<()> {
+    forget_undone(connection)?;
+
+    connection.execute(&format!("INSERT INTO history_operations (name, session) VALUES (?1, {SESSION})"), (name,))?;
+    let operation_id = connection.last_insert_rowid();
+    connection.execute("UPDATE temp.history_state SET operation_id = ?1", (operation_id,))?;
+
+    connection.execute("DELETE FROM history_changes WHERE operation_id <= ?1", (operation_id - MAX_OPERATIONS,))?;
+    connection.execute("DELETE FROM history_operations WHERE id <= ?1", (operation_id - MAX_OPERATIONS,))?;
+
+    Ok(())
+}
+
+fn forget_undone(connection: &Connection) -> Result<()> {
+    connection.execute_batch(&format!(r#"
+        DELETE FROM history_changes WHERE operation_id IN (SELECT id FROM history_operations WHERE undone = 1 AND session = {SESSION});
+        DELETE FROM history_operations WHERE undone = 1 AND session = {SESSION};
+    "#))?;
+
+    Ok(())
+}
+
+fn forget_operation(connection: &Connection, operation_id: i64) -> Result<()> {
+    connection.execute("DELETE FROM history_changes WHERE operation_id = ?1", (operation_id,))?;
+    connection.execute("DELETE FROM history_operations WHERE id = ?1", (operation_id,))?;
+    Ok(())
+}
+
+fn pause_recording(connection: &Connection) -> Result<()> {
+    connection.execute("UPDATE temp.history_state SET operation_id = NULL", ())?;
+    Ok(())
+}
+
+// Rows with a revision have to be the way the operation left them, or else someone else
+// changed them since and restoring would silently drop their change. `expected` is the
+// snapshot the row should match, None if it should be gone.
+fn check_revision(connection: &Connection, table: &str, row_id: i64, expected: Option<&str>) -> Result<()> {
+    if !table_columns(connection, table)?.iter().any(|column| column == "revision") {
+        return Ok(());
+    }
+
+    let current: Option<i64> = connection.query_row(&format!("SELECT revision FROM {table} WHERE id = ?1"), (row_id,), |row| row.get(0))
+        .optional()?;
+    let expected: Option<i64> = match expected {
+        Some(row_json) => connection.query_row("SELECT json_extract(?1, '$.revision')", (row_json,), |row| row.get(0))?,
+        None => None
+    };
+
+    match current == expected {
+        true => Ok(()),
+        false => Err(Box::new(Conflict))
+    }
+}
+
+// Returns the revision the row got, a restored row is a new write and its revision goes up
+// from the current one, that way processes holding the row never mistake it for their version.
+fn restore_row(connection: &Connection, table: &str, row_id: i64, row_json: Option<String>) -> Result<Option<i64>> {
+    let Some(row_json) = row_json else {
+        connection.execute(&format!("DELETE FROM {table} WHERE id = ?1"), (row_id,))?;
+        return Ok(None);
+    };
+
+    // an upsert instead of a replace, that way update triggers of the table still fire
+    let columns = table_columns(connection, table)?;
+    let values: Vec<String> = columns.iter()
+        .map(|column| match column.as_str() {
+            "id" => "?2".to_string(),
+            "revision" => format!("coalesce((SELECT revision FROM {table} WHERE id = ?2), json_extract(?1, '$.revision')) + 1"),
+            _ => format!("json_extract(?1, '$.{column}')")
+        })
+        .collect();
+    let assignments: Vec<String> = columns.iter()
+        .filter(|column| *column != "id")
+        .map(|column| format!("{column} = excluded.{column}"))
+        .collect();
+
+    connection.execute(&format!(r#"
+        INSERT INTO {table} ({}) SELECT {} WHERE true
+            ON CONFLICT (id) DO UPDATE SET {}
+    "#, columns.join(", "), values.join(", "), assignments.join(", ")), (row_json, row_id))?;
+
+    match columns.iter().any(|column| column == "revision") {
+        true => Ok(Some(connection.query_row(&format!("SELECT revision FROM {table} WHERE id = ?1"), (row_id,), |row| row.get(0))?)),
+        false => Ok(None)
+    }
+}
+
+// rows are restored one by one, a task may come back before the badge or parent it refers
+// to, so the foreign keys are only checked on commit
+fn defer_foreign_keys(connection: &Connection) -> Result<()> {
+    connection.pragma_update(None, "defer_foreign_keys", true)?;
+    Ok(())
+}
+
+// id, table, row id and the row before and after the change
+type Change = (i64, String, i64, Option<String>, Option<String>);
+
+// changes of the operation, `order` is ASC or DESC by id
+fn load_changes(connection: &Connection, operation_id: i64, order: &str) -> Result<Vec<Change>> {
+    let changes = connection.prepare(&format!(r#"
+        SELECT id, table_name, row_id, before, after FROM history_changes
+            WHERE operation_id = ?1
+        ORDER BY id {order}
+    "#))?
+        .query_map((operation_id,), |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    Ok(changes)
+}
+
+// reverts the latest operation of this session, returns its name or None if there is nothing
+// to undo. Fails with `Conflict` if a row it changed was changed elsewhere since, the operation
+// is dropped from the history then.
+pub fn undo(connection: &mut Connection) -> Result<Option<String>> {
+    pause_recording(connection)?;
+
+    let transaction = connection.transaction()?;
+    defer_foreign_keys(&transaction)?;
+    let operation: Option<(i64, String)> = transaction.query_row(&format!(r#"
+        SELECT id, name FROM history_operations
+            WHERE undone = 0 AND session = {SESSION} AND EXISTS (SELECT 1 FROM history_changes WHERE operation_id = history_operations.id)
+        ORDER BY id DESC
+        LIMIT 1
+    "#), (), |row| Ok((row.get(0)?, row.get(1)?))).ok();
+
+    let Some((operation_id, name)) = operation else {
+        return Ok(None);
+    };
+
+    let changes = load_changes(&transaction, operation_id, "DESC")?;
+
+    // a row has to look like the last change of the operation left it
+    let mut checked = HashSet::new();
+    for (change_id, table, row_id, before, after) in changes {
+        if checked.insert((table.clone(), row_id)) {
+            if let Err(err) = check_revision(&transaction, &table, row_id, after.as_deref()) {
+                transaction.rollback()?;
+                forget_operation(connection, operation_id)?;
+                return Err(err);
+            }
+        }
+
+        // redo and the undo of the change before compare against the revision the row has now
+        if let Some(revision) = restore_row(&transaction, &table, row_id, before)? {
+            transaction.execute("UPDATE history_changes SET before = json_set(before, '$.revision', ?1) WHERE id = ?2", (revision, change_id))?;
+            transaction.execute(r#"
+                UPDATE history_changes SET after = json_set(after, '$.revision', ?1)
+                    WHERE id = (SELECT max(id) FROM history_changes WHERE table_name = ?2 AND row_id = ?3 AND id < ?4)
+            "#, (revision, &table, row_id, change_id))?;
+        }
+    }
+
+    // operations without any change never did anything and are dropped on the way
+    transaction.execute(&format!("DELETE FROM history_operations WHERE id > ?1 AND undone = 0 AND session = {SESSION}"), (operation_id,))?;
+    transaction.execute("UPDATE history_operations SET undone = 1 WHERE id = ?1", (operation_id,))?;
+    transaction.commit()?;
+
+    Ok(Some(name))
+}
+
+// applies the oldest undone operation of this session again, returns its name or None if there
+// is nothing to redo. Fails with `Conflict` like undo, the undone operations are dropped then.
+pub fn redo(connection: &mut Connection) -> Result<Option<String>> {
+    pause_recording(connection)?;
+
+    let transaction = connection.transaction()?;
+    defer_foreign_keys(&transaction)?;
+    let operation: Option<(i64, String)> = transaction.query_row(&format!(r#"
+        SELECT id, name FROM history_operations
+            WHERE undone = 1 AND session = {SESSION}
+        ORDER BY id ASC
+        LIMIT 1
+    "#), (), |row| Ok((row.get(0)?, row.get(1)?))).ok();
+
+    let Some((operation_id, name)) = operation else {
+        return Ok(None);
+    };
+
+    let changes = load_changes(&transaction, operation_id, "ASC")?;
+
+    // a row has to look like undo left it
+    let mut checked = HashSet::new();
+    for (change_id, table, row_id, before, after) in changes {
+        if checked.insert((table.clone(), row_id)) {
+            if let Err(err) = check_revision(&transaction, &table, row_id, before.as_deref()) {
+                transaction.rollback()?;
+                forget_undone(connection)?;
+                return Err(err);
+            }
+        }
+
+        // undo and the redo of the change after compare against the revision the row has now
+        if let Some(revision) = restore_row(&transaction, &table, row_id, after)? {
+            transaction.execute("UPDATE history_changes SET after = json_set(after, '$.revision', ?1) WHERE id = ?2", (revision, change_id))?;
+            transaction.execute(r#"
+                UPDATE history_changes SET before = json_set(before, '$.revision', ?1)
+                    WHERE id = (SELECT min(id) FROM history_changes WHERE table_name = ?2 AND row_id = ?3 AND id > ?4)
+            "#, (revision, &table, row_id, change_id))?;
+        }
+    }
+
+    transaction.execute("UPDATE history_operations SET undone = 0 WHERE id = ?1", (operation_id,))?;
+    transaction.commit()?;
+
+    Ok(Some(name))
+}
@@ -0,0 +1,490 @@
+use std::{fs, path::{Path, PathBuf}, str::FromStr};
+
+use chrono::NaiveDateTime;
+use ratatui::style::Color;
+
+use crate::{cli, format::markdown::{Checklist, ChecklistItem}};
+
+use super::{search, storage::Storage, Badge, BadgeTransition, Note, Result, SearchHit, Task, TaskList, TimeEntry, TrashedTask, Dependency, BADGE_COLORS, DEFAULT_BADGES, SORT_ORDER_STEP};
+
+#[derive(Clone)]
+struct Entry {
+    task: Task,
+    checked: bool,
+    marker: char,
+    trailing: Vec<String>
+}
+
+// Keeps a single list in a Markdown checklist and writes the file back after every change, or
+// after the last one of a transaction. Badges are written by name, their colors, order and
+// transitions only live as long as the process, and so do the trash, the timestamps, the
+// tracked time and the dependencies. A badge named done that only appears in the file counts
+// as done.
+#[derive(Clone)]
+pub struct MarkdownStorage {
+    path: PathBuf,
+    list: TaskList,
+    // layout of the file, its items are replaced by `entries` when writing
+    checklist: Checklist,
+    entries: Vec<Entry>,
+    badges: Vec<Badge>,
+    badge_transitions: Vec<BadgeTransition>,
+    notes: Vec<Note>,
+    trash: Vec<TrashedTask>,
+    time_entries: Vec<TimeEntry>,
+    dependencies: Vec<Dependency>,
+    last_id: i64,
+    // everything as it was when the transaction began, the file is only written on commit
+    backup: Option<Box<MarkdownStorage>>
+}
+
+impl MarkdownStorage {
+    pub fn open(path: &Path) -> Result<Self> {
+        let content = match path.exists() {
+            true => fs::read_to_string(path)?,
+            false => String::new()
+        };
+
+        let name = path.file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "Tasks".to_string());
+
+        let mut storage = Self {
+            path: path.to_path_buf(),
+            list: TaskList { id: 0, name },
+            checklist: Checklist::parse(&content),
+            entries: vec![],
+            badges: vec![],
+            badge_transitions: vec![],
+            notes: vec![],
+            trash: vec![],
+            time_entries: vec![],
+            dependencies: vec![],
+            last_id: 0,
+            backup: None
+        };
+
+        storage.list.id = storage.next_id();
+        for (name, color, done) in DEFAULT_BADGES {
+            let badge_id = storage.badge_id(name, Some(Color::from_str(color)?));
+            storage.badges.iter_mut().filter(|badge| badge.id == badge_id).for_each(|badge| badge.done = done);
+        }
+
+        let items = std::mem::take(&mut storage.checklist.items);
+        let mut parent_ids: Vec<i64> = vec![];
+        for (idx, item) in items.into_iter().enumerate() {
+            let id = storage.next_id();
+            parent_ids.truncate(item.depth);
+
+            let badge_name = match (&item.badge, item.checked) {
+                (Some(name), _) => Some(name.as_str()),
+                (None, true) => Some("Done"),
+                (None, false) => None
+            };
+            let badge_id = badge_name.map(|name| storage.badge_id(name, None));
+
+            let note_id = item.note.map(|text| {
+                let note_id = storage.next_id();
+                storage.notes.push(Note::new(Some(note_id), text));
+                note_id
+            });
+
+            storage.entries.push(Entry {
+                task: Task {
+                    id: Some(id),
+                    text: item.text,
+                    badge_id,
+                    note_id,
+                    parent_id: parent_ids.last().copied(),
+                    due_date: item.due_date,
+                    start_date: item.start_date,
+                    recurrence: item.recurrence,
+                    sort_order: idx as i64 * SORT_ORDER_STEP,
+                    ..Default::default()
+                },
+                checked: item.checked,
+                marker: item.marker,
+                trailing: item.trailing
+            });
+            parent_ids.push(id);
+        }
+
+        Ok(storage)
+    }
+
+    fn next_id(&mut self) -> i64 {
+        self.last_id += 1;
+        self.last_id
+    }
+
+    // finds the badge by its name, unknown names become new badges
+    fn badge_id(&mut self, name: &str, color: Option<Color>) -> i64 {
+        if let Some(badge) = self.badges.iter().find(|badge| badge.name == name) {
+            return badge.id;
+        }
+
+        let id = self.next_id();
+        let color = color.unwrap_or_else(|| {
+            let color = BADGE_COLORS[self.badges.len() % BADGE_COLORS.len()];
+            Color::from_str(color).unwrap()
+        });
+        let sort_order = self.badges.len() as i64;
+        let done = name.eq_ignore_ascii_case("done");
+        self.badges.push(Badge { id, name: String::from(name), color, sort_order, done });
+
+        id
+    }
+
+    fn entry_index(&self, task_id: i64) -> Result<usize> {
+        self.entries.iter()
+            .position(|entry| entry.task.id == Some(task_id))
+            .ok_or_else(|| format!("couldn't find task {task_id}").into())
+    }
+
+    fn save(&mut self) -> Result<()> {
+        if self.backup.is_some() {
+            return Ok(());
+        }
+
+        self.entries.sort_by_key(|entry| entry.task.sort_order);
+
+        let mut depths: Vec<(i64, usize)> = vec![];
+        self.checklist.items = self.entries.iter()
+            .map(|entry| {
+                let depth = entry.task.parent_id
+                    .and_then(|parent_id| depths.iter().find(|(id, _)| *id == parent_id))
+                    .map(|(_, depth)| depth + 1)
+                    .unwrap_or(0);
+                depths.push((entry.task.id.unwrap_or_default(), depth));
+
+                // a checked item without a prefix is done already
+                let badge = entry.task.badge_id.and_then(|badge_id| self.badges.iter().find(|badge| badge.id == badge_id));
+                let badge = badge
+                    .filter(|badge| !(entry.checked && badge.is_done()))
+                    .map(|badge| badge.name.clone());
+
+                let note = entry.task.note_id
+                    .and_then(|note_id| self.notes.iter().find(|note| note.id == Some(note_id)))
+                    .map(|note| note.text.clone())
+                    .filter(|text| !text.is_empty());
+
+                ChecklistItem {
+                    depth,
+                    checked: entry.checked,
+                    badge,
+                    text: entry.task.text.clone(),
+                    note,
+                    due_date: entry.task.due_date,
+                    start_date: entry.task.start_date,
+                    recurrence: entry.task.recurrence.clone(),
+                    marker: entry.marker,
+                    trailing: entry.trailing.clone()
+                }
+            })
+            .collect();
+
+        // written next to the file first, that way it's never left half written
+        let temp_path = self.path.with_file_name(format!(".{}.tmp", self.list.name));
+        fs::write(&temp_path, self.checklist.render())?;
+        fs::rename(&temp_path, &self.path)?;
+
+        Ok(())
+    }
+
+    fn single_list(&self, list_id: i64) -> Result<()> {
+        match list_id == self.list.id {
+            true => Ok(()),
+            false => Err(format!("{} holds a single list", self.list.name).into())
+        }
+    }
+}
+
+impl Storage for MarkdownStorage {
+    fn location(&self) -> String {
+        cli::display_path(&self.path)
+    }
+
+    fn load_lists(&self) -> Result<Vec<TaskList>> {
+        Ok(vec![self.list.clone()])
+    }
+
+    fn load_badges(&self) -> Result<Vec<Badge>> {
+        Ok(self.badges.clone())
+    }
+
+    fn load_notes(&self) -> Result<Vec<Note>> {
+        Ok(self.notes.clone())
+    }
+
+    fn load_tasks(&self, list_id: i64) -> Result<Vec<Task>> {
+        if list_id != self.list.id {
+            return Ok(vec![]);
+        }
+
+        let mut tasks: Vec<Task> = self.entries.iter().map(|entry| entry.task.clone()).collect();
+        tasks.sort_by_key(|task| task.sort_order);
+
+        Ok(tasks)
+    }
+
+    fn insert_list(&mut self, _name: &str) -> Result<i64> {
+        Err(format!("{} holds a single list", self.list.name).into())
+    }
+
+    fn rename_list(&mut self, _list_id: i64, _name: &str) -> Result<()> {
+        Err("the list is named after its file".into())
+    }
+
+    fn delete_list(&mut self, _list_id: i64) -> Result<()> {
+        Err(format!("{} holds a single list", self.list.name).into())
+    }
+
+    fn insert_badge(&mut self, name: &str, color: Color) -> Result<Badge> {
+        if self.badges.iter().any(|badge| badge.name == name) {
+            return Err(format!("there's a badge named {name} already").into());
+        }
+
+        let id = self.badge_id(name, Some(color));
+        Ok(self.badges.iter().find(|badge| badge.id == id).unwrap().clone())
+    }
+
+    fn update_badge(&mut self, badge: &Badge) -> Result<()> {
+        let stored = self.badges.iter_mut()
+            .find(|stored| stored.id == badge.id)
+            .ok_or_else(|| format!("couldn't find badge {}", badge.id))?;
+        let renamed = stored.name != badge.name;
+        let done_changed = stored.done != badge.done;
+        *stored = badge.clone();
+
+        // the checkbox follows the done flag
+        self.entries.iter_mut()
+            .filter(|entry| done_changed && entry.task.badge_id == Some(badge.id))
+            .for_each(|entry| entry.checked = badge.done);
+
+        match renamed || done_changed {
+            true => self.save(),
+            false => Ok(())
+        }
+    }
+
+    // the file has no completion times, they only live in memory
+    fn update_badge_completed_at(&mut self, badge_id: i64, completed_at: Option<NaiveDateTime>) -> Result<()> {
+        let entries = self.entries.iter_mut().map(|entry| &mut entry.task);
+        let trashed = self.trash.iter_mut().map(|trashed| &mut trashed.task);
+        entries.chain(trashed)
+            .filter(|task| task.badge_id == Some(badge_id) && task.completed_at.is_some() != completed_at.is_some())
+            .for_each(|task| {
+                task.completed_at = completed_at;
+                task.revision += 1;
+            });
+
+        Ok(())
+    }
+
+    fn delete_badge(&mut self, badge_id: i64, replacement_id: Option<i64>) -> Result<()> {
+        let replacement_done = replacement_id
+            .and_then(|id| self.badges.iter().find(|badge| badge.id == id))
+            .is_some_and(Badge::is_done);
+
+        self.entries.iter_mut()
+            .filter(|entry| entry.task.badge_id == Some(badge_id))
+            .for_each(|entry| {
+                entry.task.badge_id = replacement_id;
+                entry.checked = replacement_done;
+            });
+        self.badge_transitions.retain(|transition| transition.from_id != badge_id && transition.to_id != badge_id);
+        self.badges.retain(|badge| badge.id != badge_id);
+
+        self.save()
+    }
+
+    fn load_badge_transitions(&self) -> Result<Vec<BadgeTransition>> {
+        Ok(self.badge_transitions.clone())
+    }
+
+    fn insert_badge_transition(&mut self, from_id: i64, to_id: i64) -> Result<i64> {
+        let id = self.next_id();
+        self.badge_transitions.push(BadgeTransition { id, from_id, to_id });
+
+        Ok(id)
+    }
+
+    fn delete_badge_transition(&mut self, transition_id: i64) -> Result<()> {
+        self.badge_transitions.retain(|transition| transition.id != transition_id);
+        Ok(())
+    }
+
+    fn insert_note(&mut self, note: &Note) -> Result<i64> {
+        let id = self.next_id();
+        self.notes.push(Note { id: Some(id), ..note.clone() });
+
+        Ok(id)
+    }
+
+    fn update_note(&mut self, note: &Note) -> Result<()> {
+        let stored = self.notes.iter_mut()
+            .find(|stored| stored.id == note.id)
+            .ok_or_else(|| "couldn't find note".to_string())?;
+        *stored = Note { revision: note.revision + 1, ..note.clone() };
+
+        self.save()
+    }
+
+    fn insert_task(&mut self, list_id: i64, task: &Task) -> Result<i64> {
+        self.single_list(list_id)?;
+
+        let id = self.next_id();
+        let checked = task.badge_id
+            .and_then(|badge_id| self.badges.iter().find(|badge| badge.id == badge_id))
+            .is_some_and(Badge::is_done);
+
+        self.entries.push(Entry {
+            task: Task { id: Some(id), ..task.clone() },
+            checked,
+            marker: '-',
+            trailing: vec![]
+        });
+        self.save()?;
+
+        Ok(id)
+    }
+
+    fn update_task(&mut self, task: &Task) -> Result<()> {
+        let task_id = task.id.ok_or_else(|| "task isn't stored yet".to_string())?;
+        let idx = self.entry_index(task_id)?;
+
+        // the checkbox follows the badge once the badge changes
+        if self.entries[idx].task.badge_id != task.badge_id {
+            self.entries[idx].checked = task.badge_id
+                .and_then(|badge_id| self.badges.iter().find(|badge| badge.id == badge_id))
+                .is_some_and(Badge::is_done);
+        }
+        self.entries[idx].task = Task { revision: task.revision + 1, ..task.clone() };
+
+        self.save()
+    }
+
+    fn update_tasks(&mut self, tasks: &[&Task]) -> Result<()> {
+        for task in tasks {
+            let task_id = task.id.ok_or_else(|| "task isn't stored yet".to_string())?;
+            let idx = self.entry_index(task_id)?;
+            self.entries[idx].task = Task { revision: task.revision + 1, ..(*task).clone() };
+        }
+
+        self.save()
+    }
+
+    fn trash_task(&mut self, task_id: i64, deleted_at: NaiveDateTime) -> Result<()> {
+        self.entries.sort_by_key(|entry| entry.task.sort_order);
+        let idx = self.entry_index(task_id)?;
+        let entry = self.entries.remove(idx);
+
+        self.entries.iter_mut()
+            .filter(|other| other.task.parent_id == Some(task_id))
+            .for_each(|other| other.task.parent_id = entry.task.parent_id);
+
+        // lines that followed the task stay where they were
+        match idx.checked_sub(1) {
+            Some(previous) => self.entries[previous].trailing.extend(entry.trailing),
+            None => self.checklist.head.extend(entry.trailing)
+        }
+        self.trash.push(TrashedTask { task: entry.task, list_id: self.list.id, deleted_at });
+
+        self.save()
+    }
+
+    fn load_trash(&self) -> Result<Vec<TrashedTask>> {
+        Ok(self.trash.iter().rev().cloned().collect())
+    }
+
+    fn restore_task(&mut self, task_id: i64) -> Result<()> {
+        let idx = self.trash.iter()
+            .position(|trashed| trashed.task.id == Some(task_id))
+            .ok_or_else(|| format!("task {task_id} isn't in the trash"))?;
+        let task = self.trash.remove(idx).task;
+
+        let checked = task.badge_id
+            .and_then(|badge_id| self.badges.iter().find(|badge| badge.id == badge_id))
+            .is_some_and(Badge::is_done);
+        self.entries.push(Entry { task, checked, marker: '-', trailing: vec![] });
+
+        self.save()
+    }
+
+    fn purge_tasks(&mut self, task_ids: &[i64]) -> Result<()> {
+        self.trash.retain(|trashed| !trashed.task.id.is_some_and(|id| task_ids.contains(&id)));
+        self.time_entries.retain(|entry| !task_ids.contains(&entry.task_id));
+        self.dependencies.retain(|dependency| !task_ids.contains(&dependency.task_id) && !task_ids.contains(&dependency.blocker_id));
+
+        let note_ids: Vec<i64> = self.entries.iter().map(|entry| &entry.task)
+            .chain(self.trash.iter().map(|trashed| &trashed.task))
+            .filter_map(|task| task.note_id)
+            .collect();
+        self.notes.retain(|note| note.id.is_some_and(|id| note_ids.contains(&id)));
+
+        Ok(())
+    }
+
+    fn move_tasks_to_list(&mut self, _task_ids: &[i64], list_id: i64) -> Result<()> {
+        self.single_list(list_id)
+    }
+
+    fn load_time_entries(&self) -> Result<Vec<TimeEntry>> {
+        Ok(self.time_entries.clone())
+    }
+
+    fn insert_time_entry(&mut self, entry: &TimeEntry) -> Result<i64> {
+        let id = self.next_id();
+        self.time_entries.push(TimeEntry { id: Some(id), ..entry.clone() });
+
+        Ok(id)
+    }
+
+    fn update_time_entry(&mut self, entry: &TimeEntry) -> Result<()> {
+        let stored = self.time_entries.iter_mut()
+            .find(|stored| stored.id == entry.id)
+            .ok_or("couldn't find the time entry")?;
+        *stored = entry.clone();
+
+        Ok(())
+    }
+
+    fn load_dependencies(&self) -> Result<Vec<Dependency>> {
+        Ok(self.dependencies.clone())
+    }
+
+    fn insert_dependency(&mut self, task_id: i64, blocker_id: i64) -> Result<i64> {
+        let id = self.next_id();
+        self.dependencies.push(Dependency { id, task_id, blocker_id });
+
+        Ok(id)
+    }
+
+    fn delete_dependency(&mut self, dependency_id: i64) -> Result<()> {
+        self.dependencies.retain(|dependency| dependency.id != dependency_id);
+        Ok(())
+    }
+
+    fn begin_transaction(&mut self) -> Result<()> {
+        self.backup = None;
+        self.backup = Some(Box::new(self.clone()));
+        Ok(())
+    }
+
+    fn commit_transaction(&mut self) -> Result<()> {
+        self.backup = None;
+        self.save()
+    }
+
+    fn rollback_transaction(&mut self) -> Result<()> {
+        if let Some(backup) = self.backup.take() {
+            *self = *backup;
+        }
+        Ok(())
+    }
+
+    fn search(&self, input: &str, limit: usize) -> Result<Vec<SearchHit>> {
+        let tasks = self.entries.iter().map(|entry| (self.list.id, &entry.task));
+        Ok(search::scan(tasks, &self.notes, input, limit))
+    }
+}
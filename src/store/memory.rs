@@ -0,0 +1,351 @@
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use ratatui::style::Color;
+
+use super::{search, storage::Storage, Badge, BadgeTransition, Note, Result, SearchHit, Task, TaskList, TimeEntry, TrashedTask, Dependency, DEFAULT_BADGES, SORT_ORDER_STEP};
+
+#[derive(Clone)]
+struct StoredList {
+    list: TaskList,
+    sort_order: i64
+}
+
+#[derive(Clone)]
+struct StoredTask {
+    task: Task,
+    list_id: i64,
+    deleted_at: Option<NaiveDateTime>
+}
+
+// Keeps everything in memory and forgets it on exit, meant for tools and tests that
+// shouldn't need a database file.
+#[derive(Clone)]
+pub struct MemoryStorage {
+    lists: Vec<StoredList>,
+    badges: Vec<Badge>,
+    badge_transitions: Vec<BadgeTransition>,
+    notes: Vec<Note>,
+    tasks: Vec<StoredTask>,
+    time_entries: Vec<TimeEntry>,
+    dependencies: Vec<Dependency>,
+    last_id: i64,
+    // everything as it was when the transaction began
+    backup: Option<Box<MemoryStorage>>
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        let mut storage = Self {
+            lists: vec![],
+            badges: vec![],
+            badge_transitions: vec![],
+            notes: vec![],
+            tasks: vec![],
+            time_entries: vec![],
+            dependencies: vec![],
+            last_id: 0,
+            backup: None
+        };
+
+        let list_id = storage.next_id();
+        storage.lists.push(StoredList { list: TaskList { id: list_id, name: String::from("Tasks") }, sort_order: 0 });
+
+        for (sort_order, (name, color, done)) in DEFAULT_BADGES.iter().enumerate() {
+            let id = storage.next_id();
+            let color = Color::from_str(color).unwrap();
+            storage.badges.push(Badge { id, name: String::from(*name), color, sort_order: sort_order as i64, done: *done });
+        }
+
+        storage
+    }
+
+    // ids are unique across all kinds of rows, that's simpler and nothing relies on the opposite
+    fn next_id(&mut self) -> i64 {
+        self.last_id += 1;
+        self.last_id
+    }
+
+    fn stored_task(&mut self, task_id: i64) -> Result<&mut StoredTask> {
+        self.tasks.iter_mut()
+            .find(|stored| stored.task.id == Some(task_id))
+            .ok_or_else(|| format!("couldn't find task {task_id}").into())
+    }
+
+    // drops time entries and dependencies of tasks that are gone
+    fn retain_task_relations(&mut self) {
+        let tasks = &self.tasks;
+        let exists = |task_id: i64| tasks.iter().any(|stored| stored.task.id == Some(task_id));
+        self.time_entries.retain(|entry| exists(entry.task_id));
+        self.dependencies.retain(|dependency| exists(dependency.task_id) && exists(dependency.blocker_id));
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn location(&self) -> String {
+        "in memory".to_string()
+    }
+
+    fn load_lists(&self) -> Result<Vec<TaskList>> {
+        let mut lists: Vec<&StoredList> = self.lists.iter().collect();
+        lists.sort_by_key(|stored| stored.sort_order);
+
+        Ok(lists.into_iter().map(|stored| stored.list.clone()).collect())
+    }
+
+    fn load_badges(&self) -> Result<Vec<Badge>> {
+        Ok(self.badges.clone())
+    }
+
+    fn load_notes(&self) -> Result<Vec<Note>> {
+        Ok(self.notes.clone())
+    }
+
+    fn load_tasks(&self, list_id: i64) -> Result<Vec<Task>> {
+        let mut tasks: Vec<Task> = self.tasks.iter()
+            .filter(|stored| stored.list_id == list_id && stored.deleted_at.is_none())
+            .map(|stored| stored.task.clone())
+            .collect();
+        tasks.sort_by_key(|task| task.sort_order);
+
+        Ok(tasks)
+    }
+
+    fn insert_list(&mut self, name: &str) -> Result<i64> {
+        let id = self.next_id();
+        let sort_order = self.lists.iter().map(|stored| stored.sort_order + 1).max().unwrap_or(0);
+        self.lists.push(StoredList { list: TaskList { id, name: String::from(name) }, sort_order });
+
+        Ok(id)
+    }
+
+    fn rename_list(&mut self, list_id: i64, name: &str) -> Result<()> {
+        let stored = self.lists.iter_mut()
+            .find(|stored| stored.list.id == list_id)
+            .ok_or_else(|| format!("couldn't find list {list_id}"))?;
+        stored.list.name = String::from(name);
+
+        Ok(())
+    }
+
+    fn delete_list(&mut self, list_id: i64) -> Result<()> {
+        let note_ids: Vec<i64> = self.tasks.iter()
+            .filter(|stored| stored.list_id == list_id)
+            .filter_map(|stored| stored.task.note_id)
+            .collect();
+
+        self.tasks.retain(|stored| stored.list_id != list_id);
+        // next occurrences of a recurring task share the note and may live in another list
+        let tasks = &self.tasks;
+        self.notes.retain(|note| !note.id.is_some_and(|id| note_ids.contains(&id) && !tasks.iter().any(|stored| stored.task.note_id == Some(id))));
+        self.retain_task_relations();
+        self.lists.retain(|stored| stored.list.id != list_id);
+
+        Ok(())
+    }
+
+    fn insert_badge(&mut self, name: &str, color: Color) -> Result<Badge> {
+        let id = self.next_id();
+        let sort_order = self.badges.iter().map(|badge| badge.sort_order + 1).max().unwrap_or(0);
+        let badge = Badge { id, name: String::from(name), color, sort_order, done: false };
+        self.badges.push(badge.clone());
+
+        Ok(badge)
+    }
+
+    fn update_badge(&mut self, badge: &Badge) -> Result<()> {
+        let stored = self.badges.iter_mut()
+            .find(|stored| stored.id == badge.id)
+            .ok_or_else(|| format!("couldn't find badge {}", badge.id))?;
+        *stored = badge.clone();
+
+        Ok(())
+    }
+
+    fn update_badge_completed_at(&mut self, badge_id: i64, completed_at: Option<NaiveDateTime>) -> Result<()> {
+        self.tasks.iter_mut()
+            .filter(|stored| stored.task.badge_id == Some(badge_id) && stored.task.completed_at.is_some() != completed_at.is_some())
+            .for_each(|stored| {
+                stored.task.completed_at = completed_at;
+                stored.task.revision += 1;
+            });
+
+        Ok(())
+    }
+
+    fn delete_badge(&mut self, badge_id: i64, replacement_id: Option<i64>) -> Result<()> {
+        self.tasks.iter_mut()
+            .filter(|stored| stored.task.badge_id == Some(badge_id))
+            .for_each(|stored| stored.task.badge_id = replacement_id);
+        self.badge_transitions.retain(|transition| transition.from_id != badge_id && transition.to_id != badge_id);
+        self.badges.retain(|badge| badge.id != badge_id);
+
+        Ok(())
+    }
+
+    fn load_badge_transitions(&self) -> Result<Vec<BadgeTransition>> {
+        Ok(self.badge_transitions.clone())
+    }
+
+    fn insert_badge_transition(&mut self, from_id: i64, to_id: i64) -> Result<i64> {
+        let id = self.next_id();
+        self.badge_transitions.push(BadgeTransition { id, from_id, to_id });
+
+        Ok(id)
+    }
+
+    fn delete_badge_transition(&mut self, transition_id: i64) -> Result<()> {
+        self.badge_transitions.retain(|transition| transition.id != transition_id);
+        Ok(())
+    }
+
+    fn insert_note(&mut self, note: &Note) -> Result<i64> {
+        let id = self.next_id();
+        self.notes.push(Note { id: Some(id), ..note.clone() });
+
+        Ok(id)
+    }
+
+    fn update_note(&mut self, note: &Note) -> Result<()> {
+        let stored = self.notes.iter_mut()
+            .find(|stored| stored.id == note.id)
+            .ok_or_else(|| "couldn't find note".to_string())?;
+        *stored = Note { revision: note.revision + 1, ..note.clone() };
+
+        Ok(())
+    }
+
+    fn insert_task(&mut self, list_id: i64, task: &Task) -> Result<i64> {
+        let id = self.next_id();
+        self.tasks.push(StoredTask { task: Task { id: Some(id), ..task.clone() }, list_id, deleted_at: None });
+
+        Ok(id)
+    }
+
+    fn update_task(&mut self, task: &Task) -> Result<()> {
+        let task_id = task.id.ok_or_else(|| "task isn't stored yet".to_string())?;
+        self.stored_task(task_id)?.task = Task { revision: task.revision + 1, ..task.clone() };
+
+        Ok(())
+    }
+
+    fn trash_task(&mut self, task_id: i64, deleted_at: NaiveDateTime) -> Result<()> {
+        let parent_id = self.stored_task(task_id)?.task.parent_id;
+        self.tasks.iter_mut()
+            .filter(|stored| stored.task.parent_id == Some(task_id) && stored.deleted_at.is_none())
+            .for_each(|stored| stored.task.parent_id = parent_id);
+        self.stored_task(task_id)?.deleted_at = Some(deleted_at);
+
+        Ok(())
+    }
+
+    fn load_trash(&self) -> Result<Vec<TrashedTask>> {
+        let mut trash: Vec<TrashedTask> = self.tasks.iter()
+            .filter_map(|stored| Some(TrashedTask {
+                task: stored.task.clone(),
+                list_id: stored.list_id,
+                deleted_at: stored.deleted_at?
+            }))
+            .collect();
+        trash.sort_by_key(|trashed| std::cmp::Reverse((trashed.deleted_at, trashed.task.id)));
+
+        Ok(trash)
+    }
+
+    fn restore_task(&mut self, task_id: i64) -> Result<()> {
+        self.stored_task(task_id)?.deleted_at = None;
+        Ok(())
+    }
+
+    fn purge_tasks(&mut self, task_ids: &[i64]) -> Result<()> {
+        self.tasks.retain(|stored| {
+            stored.deleted_at.is_none() || !stored.task.id.is_some_and(|id| task_ids.contains(&id))
+        });
+        self.retain_task_relations();
+
+        let note_ids: Vec<i64> = self.tasks.iter().filter_map(|stored| stored.task.note_id).collect();
+        self.notes.retain(|note| note.id.is_some_and(|id| note_ids.contains(&id)));
+
+        Ok(())
+    }
+
+    fn move_tasks_to_list(&mut self, task_ids: &[i64], list_id: i64) -> Result<()> {
+        let first_sort_order = self.tasks.iter()
+            .filter(|stored| stored.list_id == list_id)
+            .map(|stored| stored.task.sort_order + SORT_ORDER_STEP)
+            .max()
+            .unwrap_or(0);
+
+        for (offset, task_id) in task_ids.iter().enumerate() {
+            let stored = self.stored_task(*task_id)?;
+            stored.list_id = list_id;
+            stored.task.sort_order = first_sort_order + offset as i64 * SORT_ORDER_STEP;
+            if offset == 0 {
+                stored.task.parent_id = None;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn load_time_entries(&self) -> Result<Vec<TimeEntry>> {
+        Ok(self.time_entries.clone())
+    }
+
+    fn insert_time_entry(&mut self, entry: &TimeEntry) -> Result<i64> {
+        let id = self.next_id();
+        self.time_entries.push(TimeEntry { id: Some(id), ..entry.clone() });
+
+        Ok(id)
+    }
+
+    fn update_time_entry(&mut self, entry: &TimeEntry) -> Result<()> {
+        let stored = self.time_entries.iter_mut()
+            .find(|stored| stored.id == entry.id)
+            .ok_or("couldn't find the time entry")?;
+        *stored = entry.clone();
+
+        Ok(())
+    }
+
+    fn load_dependencies(&self) -> Result<Vec<Dependency>> {
+        Ok(self.dependencies.clone())
+    }
+
+    fn insert_dependency(&mut self, task_id: i64, blocker_id: i64) -> Result<i64> {
+        let id = self.next_id();
+        self.dependencies.push(Dependency { id, task_id, blocker_id });
+
+        Ok(id)
+    }
+
+    fn delete_dependency(&mut self, dependency_id: i64) -> Result<()> {
+        self.dependencies.retain(|dependency| dependency.id != dependency_id);
+        Ok(())
+    }
+
+    fn begin_transaction(&mut self) -> Result<()> {
+        self.backup = None;
+        self.backup = Some(Box::new(self.clone()));
+        Ok(())
+    }
+
+    fn commit_transaction(&mut self) -> Result<()> {
+        self.backup = None;
+        Ok(())
+    }
+
+    fn rollback_transaction(&mut self) -> Result<()> {
+        if let Some(backup) = self.backup.take() {
+            *self = *backup;
+        }
+        Ok(())
+    }
+
+    fn search(&self, input: &str, limit: usize) -> Result<Vec<SearchHit>> {
+        let tasks = self.tasks.iter()
+            .filter(|stored| stored.deleted_at.is_none())
+            .map(|stored| (stored.list_id, &stored.task));
+        Ok(search::scan(tasks, &self.notes, input, limit))
+    }
+}
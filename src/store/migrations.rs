@@ -0,0 +1,286 @@
+use rusqlite::Connection;
+
+use super::Result;
+
+// Every entry moves the schema one version forward, the database is at
+// `PRAGMA user_version = n` after the n-th migration ran. Never edit a
+// migration that was already released, append a new one instead.
+const MIGRATIONS: &[&str] = &[
+    // 1: initial schema, written to also adopt databases created before versioning
+    r#"
+        CREATE TABLE IF NOT EXISTS badges (
+            id    INTEGER PRIMARY KEY NOT NULL,
+            name  TEXT                NOT NULL,
+            color TEXT                NOT NULL /* ansi color format */
+        );
+
+        CREATE TABLE IF NOT EXISTS notes (
+            id   INTEGER PRIMARY KEY NOT NULL,
+            text TEXT                NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS tasks (
+            id         INTEGER PRIMARY KEY NOT NULL,
+            text       TEXT                NOT NULL,
+            badge_id   INTEGER,
+            note_id    INTEGER,
+            sort_order INTEGER NOT NULL,
+
+            FOREIGN KEY (badge_id) REFERENCES badges (id),
+            FOREIGN KEY (note_id) REFERENCES notes (id)
+        );
+
+        INSERT INTO badges (name, color)
+            SELECT column1, column2 FROM (VALUES
+                ('TODO', '#FF9B9B'),
+                ('In Progress', '#FFD6A5'),
+                ('Done', '#CBFFA9')
+            )
+        WHERE (SELECT count(*) FROM badges) = 0;
+    "#,
+
+    // 2: subtasks
+    r#"
+        ALTER TABLE tasks ADD COLUMN parent_id INTEGER REFERENCES tasks (id);
+    "#,
+
+    // 3: due and start dates
+    r#"
+        ALTER TABLE tasks ADD COLUMN due_date TEXT; /* YYYY-MM-DD */
+        ALTER TABLE tasks ADD COLUMN start_date TEXT; /* YYYY-MM-DD */
+    "#,
+
+    // 4: multiple lists, existing tasks end up in the first one
+    r#"
+        CREATE TABLE lists (
+            id         INTEGER PRIMARY KEY NOT NULL,
+            name       TEXT                NOT NULL,
+            sort_order INTEGER             NOT NULL
+        );
+
+        INSERT INTO lists (name, sort_order) VALUES ('Tasks', 0);
+
+        ALTER TABLE tasks ADD COLUMN list_id INTEGER REFERENCES lists (id);
+        UPDATE tasks SET list_id = (SELECT min(id) FROM lists);
+    "#,
+
+    // 5: undo history, rows are stored as json objects
+    r#"
+        CREATE TABLE history_operations (
+            id     INTEGER PRIMARY KEY NOT NULL,
+            name   TEXT                NOT NULL,
+            undone INTEGER             NOT NULL DEFAULT 0
+        );
+
+        CREATE TABLE history_changes (
+            id           INTEGER PRIMARY KEY NOT NULL,
+            operation_id INTEGER             NOT NULL,
+            table_name   TEXT                NOT NULL,
+            row_id       INTEGER             NOT NULL,
+            before       TEXT, /* NULL if the row was inserted */
+            after        TEXT, /* NULL if the row was deleted */
+
+            FOREIGN KEY (operation_id) REFERENCES history_operations (id)
+        );
+    "#,
+
+    // 6: full text search over task texts and their notes, the rowid is the task id
+    r#"
+        CREATE VIRTUAL TABLE task_search USING fts5 (text, note);
+
+        INSERT INTO task_search (rowid, text, note)
+            SELECT tasks.id, tasks.text, coalesce(notes.text, '')
+            FROM tasks LEFT JOIN notes ON notes.id = tasks.note_id;
+
+        CREATE TRIGGER task_search_task_insert AFTER INSERT ON tasks
+        BEGIN
+            INSERT INTO task_search (rowid, text, note)
+                VALUES (NEW.id, NEW.text, coalesce((SELECT text FROM notes WHERE id = NEW.note_id), ''));
+        END;
+
+        CREATE TRIGGER task_search_task_update AFTER UPDATE OF text, note_id ON tasks
+        BEGIN
+            UPDATE task_search
+                SET text = NEW.text, note = coalesce((SELECT text FROM notes WHERE id = NEW.note_id), '')
+            WHERE rowid = NEW.id;
+        END;
+
+        CREATE TRIGGER task_search_task_delete AFTER DELETE ON tasks
+        BEGIN
+            DELETE FROM task_search WHERE rowid = OLD.id;
+        END;
+
+        CREATE TRIGGER task_search_note_update AFTER UPDATE OF text ON notes
+        BEGIN
+            UPDATE task_search
+                SET note = NEW.text
+            WHERE rowid IN (SELECT id FROM tasks WHERE note_id = NEW.id);
+        END;
+
+        CREATE TRIGGER task_search_note_delete AFTER DELETE ON notes
+        BEGIN
+            UPDATE task_search
+                SET note = ''
+            WHERE rowid IN (SELECT id FROM tasks WHERE note_id = OLD.id);
+        END;
+    "#,
+
+    // 7: user defined badge order, seeded badges keep their creation order
+    r#"
+        ALTER TABLE badges ADD COLUMN sort_order INTEGER NOT NULL DEFAULT 0;
+        UPDATE badges SET sort_order = (SELECT count(*) FROM badges AS earlier WHERE earlier.id < badges.id);
+    "#,
+
+    // 8: gaps between the sort orders of tasks, see `SORT_ORDER_STEP`
+    r#"
+        UPDATE tasks SET sort_order = sort_order * 1024;
+    "#,
+
+    // 9: revisions to notice rows that were changed by another process, see `Conflict`
+    r#"
+        ALTER TABLE tasks ADD COLUMN revision INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE notes ADD COLUMN revision INTEGER NOT NULL DEFAULT 0;
+    "#,
+
+    // 10: trash, deleted tasks keep their row until they are purged
+    r#"
+        ALTER TABLE tasks ADD COLUMN deleted_at TEXT;
+    "#,
+
+    // 11: timestamps, rows from before stay without them
+    r#"
+        ALTER TABLE tasks ADD COLUMN created_at TEXT;
+        ALTER TABLE tasks ADD COLUMN updated_at TEXT;
+        ALTER TABLE tasks ADD COLUMN completed_at TEXT;
+        ALTER TABLE notes ADD COLUMN created_at TEXT;
+        ALTER TABLE notes ADD COLUMN updated_at TEXT;
+    "#,
+
+    // 12: recurrence rules in their written form, see `Recurrence`
+    r#"
+        ALTER TABLE tasks ADD COLUMN recurrence TEXT;
+    "#,
+
+    // 13: time tracked per task, an entry without a stop time is still running
+    r#"
+        CREATE TABLE time_entries (
+            id         INTEGER PRIMARY KEY NOT NULL,
+            task_id    INTEGER             NOT NULL,
+            started_at TEXT                NOT NULL,
+            stopped_at TEXT,
+
+            FOREIGN KEY (task_id) REFERENCES tasks (id)
+        );
+
+        CREATE INDEX time_entries_task_id ON time_entries (task_id);
+    "#,
+
+    // 14: tasks waiting for other tasks to be done
+    r#"
+        CREATE TABLE task_dependencies (
+            id         INTEGER PRIMARY KEY NOT NULL,
+            task_id    INTEGER             NOT NULL,
+            blocker_id INTEGER             NOT NULL,
+
+            UNIQUE (task_id, blocker_id),
+            FOREIGN KEY (task_id) REFERENCES tasks (id),
+            FOREIGN KEY (blocker_id) REFERENCES tasks (id)
+        );
+    "#,
+
+    // 15: badges that complete a task, and which badges may follow which, a badge without
+    // transitions may be followed by any
+    r#"
+        ALTER TABLE badges ADD COLUMN done INTEGER NOT NULL DEFAULT 0;
+        UPDATE badges SET done = 1 WHERE lower(name) = 'done';
+
+        CREATE TABLE badge_transitions (
+            id      INTEGER PRIMARY KEY NOT NULL,
+            from_id INTEGER             NOT NULL,
+            to_id   INTEGER             NOT NULL,
+
+            UNIQUE (from_id, to_id),
+            FOREIGN KEY (from_id) REFERENCES badges (id),
+            FOREIGN KEY (to_id) REFERENCES badges (id)
+        );
+    "#,
+
+    // 16: the connection an operation was made on, undo only picks up its own operations
+    r#"
+        ALTER TABLE history_operations ADD COLUMN session TEXT;
+    "#,
+];
+
+pub fn latest_version() -> usize {
+    MIGRATIONS.len()
+}
+
+pub fn schema_version(connection: &Connection) -> Result<usize> {
+    let version: i64 = connection.query_row("PRAGMA user_version", (), |row| row.get(0))?;
+    Ok(version as usize)
+}
+
+pub fn migrate(connection: &mut Connection) -> Result<()> {
+    let current_version = schema_version(connection)?;
+    if current_version > latest_version() {
+        return Err(format!(
+            "database schema version {current_version} is newer than the supported version {}, please update letter",
+            latest_version()
+        ).into());
+    }
+
+    for (idx, migration) in MIGRATIONS.iter().enumerate().skip(current_version) {
+        let version = idx + 1;
+
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(migration)
+            .map_err(|err| format!("migration to schema version {version} failed: {err}"))?;
+        transaction.pragma_update(None, "user_version", version as i64)?;
+        transaction.commit()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrates_a_new_database() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate(&mut connection).unwrap();
+        assert_eq!(schema_version(&connection).unwrap(), latest_version());
+
+        // running again changes nothing
+        migrate(&mut connection).unwrap();
+        let badges: i64 = connection.query_row("SELECT count(*) FROM badges", (), |row| row.get(0)).unwrap();
+        assert_eq!(badges, 3);
+    }
+
+    #[test]
+    fn adopts_a_database_from_before_versioning() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch(r#"
+            CREATE TABLE badges (id INTEGER PRIMARY KEY NOT NULL, name TEXT NOT NULL, color TEXT NOT NULL);
+            CREATE TABLE notes (id INTEGER PRIMARY KEY NOT NULL, text TEXT NOT NULL);
+            CREATE TABLE tasks (id INTEGER PRIMARY KEY NOT NULL, text TEXT NOT NULL, badge_id INTEGER, note_id INTEGER, sort_order INTEGER NOT NULL);
+            INSERT INTO badges (name, color) VALUES ('Waiting', '#FFFFFF');
+            INSERT INTO tasks (text, badge_id, sort_order) VALUES ('call mom', 1, 0);
+        "#).unwrap();
+
+        migrate(&mut connection).unwrap();
+
+        let badges: i64 = connection.query_row("SELECT count(*) FROM badges", (), |row| row.get(0)).unwrap();
+        let text: String = connection.query_row("SELECT text FROM tasks WHERE badge_id = 1", (), |row| row.get(0)).unwrap();
+        assert_eq!((badges, text.as_str()), (1, "call mom"));
+    }
+
+    #[test]
+    fn refuses_newer_databases() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection.pragma_update(None, "user_version", latest_version() as i64 + 1).unwrap();
+
+        assert!(migrate(&mut connection).is_err());
+    }
+}
@@ -1,13 +1,60 @@
-use std::{collections::HashMap, str::FromStr};
+use std::{cmp::Ordering, collections::HashMap, error::Error, fmt::Display, str::FromStr};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
 use ratatui::style::Color;
-use rusqlite::{Connection, Row};
+use rusqlite::Row;
+
+use crate::dates::{self, Recurrence};
+
+mod migrations;
+mod history;
+mod search;
+mod storage;
+mod sqlite;
+mod memory;
+mod markdown;
+mod fsck;
+mod dump;
+
+pub use search::{SearchHit, MATCH_START, MATCH_END};
+pub use storage::Storage;
+pub use sqlite::SqliteStorage;
+pub use memory::MemoryStorage;
+pub use markdown::MarkdownStorage;
+pub use fsck::check_database;
+pub use dump::{export_database, import_database};
 
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+// tasks are spread out this far so most inserts and moves only touch a single row
+const SORT_ORDER_STEP: i64 = 1024;
+
+// badges created on the fly, by name only, get one of these
+const BADGE_COLORS: [&str; 5] = ["#A0C4FF", "#BDB2FF", "#FFC6FF", "#9BF6FF", "#FDFFB6"];
+
+// the badges every new database or file starts with
+const DEFAULT_BADGES: [(&str, &str, bool); 3] = [("TODO", "#FF9B9B", false), ("In Progress", "#FFD6A5", false), ("Done", "#CBFFA9", true)];
+
+// A task or note was changed by someone else since it was loaded, nothing was written.
+// Reloading brings in their version.
+#[derive(Debug)]
+pub struct Conflict;
+
+impl Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("changed elsewhere in the meantime")
+    }
+}
+
+impl Error for Conflict {}
+
+#[derive(Clone)]
 pub struct Badge {
     pub id: i64,
     pub name: String,
-    pub color: Color
+    pub color: Color,
+    pub sort_order: i64,
+    // tasks with a done badge count as completed
+    pub done: bool
 }
 
 impl Badge {
@@ -17,21 +64,59 @@ impl Badge {
         let badge_color_str: String = row.get("color")?;
 
         let badge_color: Color = Color::from_str(&badge_color_str)?;
+        let badge_sort_order = row.get("sort_order")?;
+        let badge_done = row.get("done")?;
 
         Ok(Badge {
             id: badge_id,
             name: badge_name,
-            color: badge_color
+            color: badge_color,
+            sort_order: badge_sort_order,
+            done: badge_done
         })
     }
+
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+}
+
+// a task with the `from_id` badge may get the `to_id` badge next
+#[derive(Clone)]
+pub struct BadgeTransition {
+    pub id: i64,
+    pub from_id: i64,
+    pub to_id: i64
 }
 
-#[derive(PartialEq, Eq)]
+impl BadgeTransition {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            from_id: row.get("from_id")?,
+            to_id: row.get("to_id")?
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
 pub struct Task {
     pub id: Option<i64>,
     pub text: String,
     pub badge_id: Option<i64>,
-    pub note_id: Option<i64>
+    pub note_id: Option<i64>,
+    pub parent_id: Option<i64>,
+    pub due_date: Option<NaiveDate>,
+    pub start_date: Option<NaiveDate>,
+    pub sort_order: i64,
+    // counts the writes to the task, a write expects the revision it was loaded with
+    pub revision: i64,
+    pub created_at: Option<NaiveDateTime>,
+    pub updated_at: Option<NaiveDateTime>,
+    // when the task got a done badge, none while it has another one
+    pub completed_at: Option<NaiveDateTime>,
+    // completing the task creates the next occurrence, which takes over the rule
+    pub recurrence: Option<Recurrence>
 }
 
 impl Task {
@@ -40,12 +125,30 @@ impl Task {
         let task_text = row.get("text")?;
         let task_badge_id = row.get("badge_id")?;
         let task_note_id = row.get("note_id")?;
+        let task_parent_id = row.get("parent_id")?;
+        let task_due_date = row.get("due_date")?;
+        let task_start_date = row.get("start_date")?;
+        let task_sort_order = row.get("sort_order")?;
+        let task_revision = row.get("revision")?;
+        let task_created_at = row.get("created_at")?;
+        let task_updated_at = row.get("updated_at")?;
+        let task_completed_at = row.get("completed_at")?;
+        let task_recurrence: Option<String> = row.get("recurrence")?;
 
         Ok(Self {
             id: Some(task_id),
             text: task_text,
             badge_id: task_badge_id,
             note_id: task_note_id,
+            parent_id: task_parent_id,
+            due_date: task_due_date,
+            start_date: task_start_date,
+            sort_order: task_sort_order,
+            revision: task_revision,
+            created_at: task_created_at,
+            updated_at: task_updated_at,
+            completed_at: task_completed_at,
+            recurrence: task_recurrence.map(|rule| rule.parse()).transpose()?
         })
     }
 }
@@ -56,28 +159,47 @@ impl Default for Task {
             id: None,
             text: String::new(),
             badge_id: None,
-            note_id: None
+            note_id: None,
+            parent_id: None,
+            due_date: None,
+            start_date: None,
+            sort_order: 0,
+            revision: 0,
+            created_at: None,
+            updated_at: None,
+            completed_at: None,
+            recurrence: None
         }
     }
 }
 
+#[derive(Clone)]
 pub struct Note {
     pub id: Option<i64>,
     pub text: String,
+    pub revision: i64,
+    pub created_at: Option<NaiveDateTime>,
+    pub updated_at: Option<NaiveDateTime>
 }
 
 impl Note {
     fn new(id: Option<i64>, text: String) -> Self {
-        Self { id, text }
+        Self { id, text, ..Default::default() }
     }
 
     fn from_row(row: &Row) -> Result<Self> {
         let note_id = row.get("id")?;
         let note_text = row.get("text")?;
+        let note_revision = row.get("revision")?;
+        let note_created_at = row.get("created_at")?;
+        let note_updated_at = row.get("updated_at")?;
 
         Ok(Self {
             id: Some(note_id),
             text: note_text,
+            revision: note_revision,
+            created_at: note_created_at,
+            updated_at: note_updated_at
         })
     }
 }
@@ -87,226 +209,1169 @@ impl Default for Note {
         Self {
             id: None,
             text: String::new(),
+            revision: 0,
+            created_at: None,
+            updated_at: None
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct TaskList {
+    pub id: i64,
+    pub name: String
+}
+
+impl TaskList {
+    fn from_row(row: &Row) -> Result<Self> {
+        let list_id = row.get("id")?;
+        let list_name = row.get("name")?;
+
+        Ok(Self {
+            id: list_id,
+            name: list_name
+        })
+    }
+}
+
+// a deleted task, it keeps its list, parent and sort order to be restored at the same place
+#[derive(Clone)]
+pub struct TrashedTask {
+    pub task: Task,
+    pub list_id: i64,
+    pub deleted_at: NaiveDateTime
+}
+
+impl TrashedTask {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(Self {
+            task: Task::from_row(row)?,
+            list_id: row.get("list_id")?,
+            deleted_at: row.get("deleted_at")?
+        })
+    }
+}
+
+// time spent on a task, the entry without a stop time is the running timer
+#[derive(Clone)]
+pub struct TimeEntry {
+    pub id: Option<i64>,
+    pub task_id: i64,
+    pub started_at: NaiveDateTime,
+    pub stopped_at: Option<NaiveDateTime>
+}
+
+impl TimeEntry {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            task_id: row.get("task_id")?,
+            started_at: row.get("started_at")?,
+            stopped_at: row.get("stopped_at")?
+        })
+    }
+
+    // a running entry counts up to `now`
+    pub fn duration(&self, now: NaiveDateTime) -> Duration {
+        (self.stopped_at.unwrap_or(now) - self.started_at).max(Duration::zero())
+    }
+}
+
+// `task_id` can't be started before `blocker_id` is done
+#[derive(Clone)]
+pub struct Dependency {
+    pub id: i64,
+    pub task_id: i64,
+    pub blocker_id: i64
+}
+
+impl Dependency {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            task_id: row.get("task_id")?,
+            blocker_id: row.get("blocker_id")?
+        })
+    }
+}
+
+// A task read from another format, its badge is only known by name.
+pub struct ImportedTask {
+    pub task: Task,
+    // 0 at the top level, subtasks directly follow their parent
+    pub depth: usize,
+    // a badge of that name is created if there's none yet
+    pub badge: Option<String>,
+    // a done task without a badge name gets the first done badge
+    pub done: bool,
+    pub note: Option<String>
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    // after the tasks of the list
+    Append,
+    // the tasks of the list go to the trash first
+    Replace
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    // oldest first
+    Created,
+    // most recent first
+    Updated,
+    Completed,
+    // earliest first
+    DueDate,
+    // tasks with a done badge go to the bottom, the rest keeps its order
+    DoneLast
+}
+
+impl SortKey {
+    pub const ALL: [SortKey; 5] = [SortKey::Created, SortKey::Updated, SortKey::Completed, SortKey::DueDate, SortKey::DoneLast];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SortKey::Created => "created",
+            SortKey::Updated => "updated",
+            SortKey::Completed => "completed",
+            SortKey::DueDate => "due date",
+            SortKey::DoneLast => "done last"
+        }
+    }
+
+    // tasks without a value go last
+    fn compare(&self, store: &TaskStore, a: &Task, b: &Task) -> Ordering {
+        fn by<T: Ord>(a: Option<T>, b: Option<T>, newest_first: bool) -> Ordering {
+            match (a, b, newest_first) {
+                (Some(a), Some(b), false) => a.cmp(&b),
+                (Some(a), Some(b), true) => b.cmp(&a),
+                (a, b, _) => a.is_none().cmp(&b.is_none())
+            }
+        }
+
+        match self {
+            SortKey::Created => by(a.created_at, b.created_at, false),
+            SortKey::Updated => by(a.updated_at, b.updated_at, true),
+            SortKey::Completed => by(a.completed_at, b.completed_at, true),
+            SortKey::DueDate => by(a.due_date, b.due_date, false),
+            SortKey::DoneLast => store.is_done(a).cmp(&store.is_done(b))
         }
     }
 }
 
 pub struct TaskStore {
-    connection: Connection,
+    storage: Box<dyn Storage>,
+    current_list_id: i64,
+    generation: u64,
+    // set while the steps of an operation run, they don't start operations of their own
+    in_operation: bool,
 
     // TODO make private
+    pub lists: Vec<TaskList>,
     pub badges: HashMap<i64, Badge>,
+    badge_transitions: Vec<BadgeTransition>,
     pub notes: HashMap<i64, Note>,
-    pub tasks: Vec<Task>
+    pub tasks: Vec<Task>,
+    // of every list, oldest first
+    time_entries: Vec<TimeEntry>,
+    dependencies: Vec<Dependency>
 }
 
 impl TaskStore {
-    pub fn new(connection: Connection) -> Self {
+    pub fn new(storage: Box<dyn Storage>) -> Self {
         TaskStore {
-            connection,
+            storage,
+            current_list_id: 0,
+            generation: 0,
+            in_operation: false,
+            lists: vec![],
             badges: HashMap::new(),
+            badge_transitions: vec![],
             notes: HashMap::new(),
-            tasks: vec![]
-        }
-    }
-
-    fn ensure_proper_setup(&mut self) -> Result<()> {
-        self.connection.execute(r#"
-            CREATE TABLE IF NOT EXISTS badges (
-                id    INTEGER PRIMARY KEY NOT NULL,
-                name  TEXT                NOT NULL,
-                color TEXT                NOT NULL /* ansi color format */
-            );
-        "#, ())?;
-
-        self.connection.execute(r#"
-            CREATE TABLE IF NOT EXISTS notes (
-                id   INTEGER PRIMARY KEY NOT NULL,
-                text TEXT                NOT NULL
-            );
-        "#, ())?;
-
-        self.connection.execute(r#"
-            CREATE TABLE IF NOT EXISTS tasks (
-                id         INTEGER PRIMARY KEY NOT NULL,
-                text       TEXT                NOT NULL,
-                badge_id   INTEGER,
-                note_id    INTEGER,
-                sort_order INTEGER NOT NULL,
-
-                FOREIGN KEY (badge_id) REFERENCES badges (id),
-                FOREIGN KEY (note_id) REFERENCES notes (id)
-            );
-        "#, ())?;
-
-        let row_count: i64 = self.connection.query_row("SELECT COUNT(*) FROM badges", (), |row| row.get(0))?;
-        if row_count >= 1 {
-            return Ok(())
+            tasks: vec![],
+            time_entries: vec![],
+            dependencies: vec![]
         }
+    }
 
-        self.connection.execute(r#"
-            INSERT INTO badges (name, color)
-                SELECT 'TODO', '#FF9B9B'
-                UNION ALL
-                SELECT 'In Progress', '#FFD6A5'
-                UNION ALL
-                SELECT 'Done', '#CBFFA9'
-            WHERE (SELECT count(*) FROM badges) = 0;
-        "#, ())?;
-
-        Ok(())
+    // changes whenever `tasks` got replaced as a whole instead of edited in place, views
+    // over the tasks have to be rebuilt then
+    pub fn generation(&self) -> u64 {
+        self.generation
     }
 
     pub fn fetch_data(&mut self) -> Result<()> {
-        self.ensure_proper_setup()?;
-
-        self.badges = self.connection.prepare("SELECT * FROM badges")?
-            .query_map([], |row| {
-                Badge::from_row(row)
-                    .map_err(|_| rusqlite::Error::ExecuteReturnedResults)
-            })?
-            .filter_map(|badge| badge.ok())
+        self.lists = self.storage.load_lists()?;
+
+        if !self.lists.iter().any(|list| list.id == self.current_list_id) {
+            self.current_list_id = self.lists.first().map(|list| list.id).ok_or_else(|| "database has no lists".to_string())?;
+        }
+
+        self.badges = self.storage.load_badges()?
+            .into_iter()
             .map(|badge| (badge.id, badge))
             .collect();
+        self.badge_transitions = self.storage.load_badge_transitions()?;
 
-        self.notes = self.connection.prepare("SELECT * FROM notes")?
-            .query_map([], |row| {
-                Note::from_row(row)
-                    .map_err(|_| rusqlite::Error::ExecuteReturnedResults)
-            })?
-            .filter_map(|note| note.ok())
+        self.notes = self.storage.load_notes()?
+            .into_iter()
             .map(|note| (note.id.unwrap(), note))
             .collect();
 
-        self.tasks = self.connection.prepare("SELECT * FROM tasks ORDER BY sort_order")?
-            .query_map([], |row| {
-                Task::from_row(row)
-                    .map_err(|_| rusqlite::Error::ExecuteReturnedResults)
-            })?
-            .filter_map(|task| task.ok())
-            .collect();
+        self.time_entries = self.storage.load_time_entries()?;
+        self.dependencies = self.storage.load_dependencies()?;
+
+        self.fetch_tasks()
+    }
+
+    fn fetch_tasks(&mut self) -> Result<()> {
+        self.tasks = self.storage.load_tasks(self.current_list_id)?;
+        self.generation += 1;
 
         Ok(())
     }
 
-    fn insert_task(&mut self, sort_index: i64, task: &Task) -> Result<()> {
-        self.connection.execute("UPDATE tasks SET sort_order = sort_order + 1 WHERE sort_order >= ?1", (sort_index,))?;
-        self.connection.execute("INSERT INTO tasks (text, badge_id, sort_order) VALUES (?1, ?2, ?3)", (&task.text, task.badge_id, sort_index))?;
+    // reloads everything if another process wrote to the storage, returns whether it did
+    pub fn reload_if_changed(&mut self) -> Result<bool> {
+        if !self.storage.changed_elsewhere()? {
+            return Ok(false);
+        }
+
+        self.fetch_data()?;
+        Ok(true)
+    }
+
+    fn begin_operation(&mut self, name: &str) -> Result<()> {
+        if self.in_operation {
+            return Ok(());
+        }
+
+        self.storage.begin_operation(name)
+    }
+
+    // runs the steps of an operation in one transaction, if any of them fails nothing gets
+    // written and everything is loaded again since the steps may have changed it in memory
+    // within another operation the steps simply become part of that one
+    fn operation<T>(&mut self, name: &str, steps: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.in_operation {
+            return steps(self);
+        }
+
+        self.storage.begin_transaction()?;
+        let result = self.begin_operation(name)
+            .and_then(|_| {
+                self.in_operation = true;
+                let result = steps(self);
+                self.in_operation = false;
+                result
+            })
+            .and_then(|value| self.storage.commit_transaction().map(|_| value));
+
+        if result.is_err() {
+            self.storage.rollback_transaction()?;
+            self.fetch_data()?;
+        }
+
+        result
+    }
+
+    // reverts the latest operation and reloads everything, returns the name of the operation
+    // after a `Conflict` the version of whoever changed the rows is loaded
+    pub fn undo(&mut self) -> Result<Option<String>> {
+        let operation = self.storage.undo();
+        if !matches!(operation, Ok(None)) {
+            self.fetch_data()?;
+        }
+
+        operation
+    }
+
+    pub fn redo(&mut self) -> Result<Option<String>> {
+        let operation = self.storage.redo();
+        if !matches!(operation, Ok(None)) {
+            self.fetch_data()?;
+        }
+
+        operation
+    }
+
+    pub fn current_list(&self) -> Option<&TaskList> {
+        self.lists.iter().find(|list| list.id == self.current_list_id)
+    }
+
+    pub fn switch_list(&mut self, list_id: i64) -> Result<()> {
+        if !self.lists.iter().any(|list| list.id == list_id) {
+            return Err(format!("list {list_id} doesn't exist").into());
+        }
+
+        self.current_list_id = list_id;
+        self.fetch_tasks()
+    }
+
+    pub fn create_list(&mut self, name: &str) -> Result<i64> {
+        self.begin_operation("create list")?;
+
+        let id = self.storage.insert_list(name)?;
+        self.lists.push(TaskList { id, name: String::from(name) });
+
+        Ok(id)
+    }
+
+    pub fn rename_list(&mut self, list_id: i64, name: &str) -> Result<()> {
+        self.begin_operation("rename list")?;
+
+        self.storage.rename_list(list_id, name)?;
+
+        let list = self.lists.iter_mut().find(|list| list.id == list_id).ok_or_else(|| "couldn't find list".to_string())?;
+        list.name = String::from(name);
 
         Ok(())
     }
 
-    pub fn create_task_at(&mut self, index: i64, task: Task) -> Result<()> {
-        self.insert_task(index, &task)?;
-        self.tasks.insert(index as usize, task);
+    // removes the list together with its tasks and their notes
+    pub fn delete_list(&mut self, list_id: i64) -> Result<()> {
+        if self.lists.len() <= 1 {
+            return Err("the last list can't be deleted".into());
+        }
+
+        self.operation("delete list", |store| {
+            store.storage.delete_list(list_id)?;
+
+            store.lists.retain(|list| list.id != list_id);
+            store.notes = store.storage.load_notes()?
+                .into_iter()
+                .map(|note| (note.id.unwrap(), note))
+                .collect();
+            store.time_entries = store.storage.load_time_entries()?;
+            store.dependencies = store.storage.load_dependencies()?;
+
+            if store.current_list_id == list_id {
+                store.current_list_id = store.lists[0].id;
+                store.fetch_tasks()?;
+            }
+
+            Ok(())
+        })
+    }
+
+    // moves the task with all of its subtasks to the end of another list, badges and notes stay attached
+    pub fn move_task_to_list(&mut self, task_id: i64, list_id: i64) -> Result<()> {
+        if list_id == self.current_list_id {
+            return Ok(());
+        }
+
+        let idx = self.task_index(task_id)?;
+        let end = self.subtree_end(idx);
+        let task_ids: Vec<i64> = self.tasks[idx..end].iter().filter_map(|task| task.id).collect();
+
+        self.operation("move task to list", |store| {
+            store.storage.move_tasks_to_list(&task_ids, list_id)?;
+
+            // dependencies only link tasks of the same list
+            let crossing: Vec<i64> = store.dependencies.iter()
+                .filter(|dependency| task_ids.contains(&dependency.task_id) != task_ids.contains(&dependency.blocker_id))
+                .map(|dependency| dependency.id)
+                .collect();
+            for dependency_id in &crossing {
+                store.storage.delete_dependency(*dependency_id)?;
+            }
+            store.dependencies.retain(|dependency| !crossing.contains(&dependency.id));
+
+            store.tasks.drain(idx..end);
+            store.generation += 1;
+
+            Ok(())
+        })
+    }
+
+    // badges in the order the user arranged them
+    pub fn sorted_badges(&self) -> Vec<&Badge> {
+        let mut badges: Vec<&Badge> = self.badges.values().collect();
+        badges.sort_by_key(|badge| (badge.sort_order, badge.id));
+        badges
+    }
+
+    pub fn create_badge(&mut self, name: &str, color: Color) -> Result<i64> {
+        color_to_string(color)?;
+        self.begin_operation("create badge")?;
+
+        let badge = self.storage.insert_badge(name, color)?;
+        let id = badge.id;
+        self.badges.insert(id, badge);
+
+        Ok(id)
+    }
+
+    pub fn rename_badge(&mut self, badge_id: i64, name: &str) -> Result<()> {
+        let badge = self.badges.get(&badge_id).ok_or_else(|| "couldn't find badge".to_string())?;
+        let badge = Badge { name: String::from(name), ..badge.clone() };
+
+        self.begin_operation("rename badge")?;
+        self.storage.update_badge(&badge)?;
+        self.badges.insert(badge_id, badge);
 
         Ok(())
     }
 
-    pub fn create_task(&mut self, task: Task) -> Result<()> {
-        let index = self.tasks.len() as i64;
-        self.create_task_at(index, task)?;
+    pub fn recolor_badge(&mut self, badge_id: i64, color: Color) -> Result<()> {
+        color_to_string(color)?;
+        let badge = self.badges.get(&badge_id).ok_or_else(|| "couldn't find badge".to_string())?;
+        let badge = Badge { color, ..badge.clone() };
+
+        self.begin_operation("recolor badge")?;
+        self.storage.update_badge(&badge)?;
+        self.badges.insert(badge_id, badge);
 
         Ok(())
     }
 
-    pub fn get_or_create_note_id(&mut self, idx_sort_order: i64) -> Result<i64> {
-        let task = self.tasks.get(idx_sort_order as usize).ok_or_else(|| "couldn't access task".to_string())?;
-        match task.note_id {
-            Some(note_id) => return Ok(note_id),
+    pub fn set_badge_done(&mut self, badge_id: i64, done: bool) -> Result<()> {
+        let badge = self.badges.get(&badge_id).ok_or_else(|| "couldn't find badge".to_string())?;
+        let badge = Badge { done, ..badge.clone() };
+
+        self.operation("change done badge", |store| {
+            store.storage.update_badge(&badge)?;
+            store.badges.insert(badge_id, badge);
+            store.update_badge_completed_at(badge_id, done)
+        })
+    }
+
+    // tasks carrying the badge get completed now or lose their completion time, depending on
+    // whether it's a done badge now
+    fn update_badge_completed_at(&mut self, badge_id: i64, done: bool) -> Result<()> {
+        let completed_at = Some(dates::now()).filter(|_| done);
+        self.storage.update_badge_completed_at(badge_id, completed_at)?;
+
+        self.tasks.iter_mut()
+            .filter(|task| task.badge_id == Some(badge_id) && task.completed_at.is_some() != done)
+            .for_each(|task| {
+                task.completed_at = completed_at;
+                task.revision += 1;
+            });
+
+        Ok(())
+    }
+
+    // badges a task with the badge may get next, every other one if there are no transitions
+    pub fn next_badge_ids(&self, badge_id: i64) -> Vec<i64> {
+        let transitions: Vec<i64> = self.badge_transitions.iter()
+            .filter(|transition| transition.from_id == badge_id)
+            .map(|transition| transition.to_id)
+            .collect();
+
+        self.sorted_badges().iter()
+            .map(|badge| badge.id)
+            .filter(|id| *id != badge_id && (transitions.is_empty() || transitions.contains(id)))
+            .collect()
+    }
+
+    // tasks without a badge may get any, keeping the badge is always fine
+    pub fn can_change_badge(&self, from_id: Option<i64>, to_id: i64) -> bool {
+        match from_id {
+            Some(from_id) => from_id == to_id || self.next_badge_ids(from_id).contains(&to_id),
+            None => true
+        }
+    }
+
+    // whether the transitions of the badge were set up, unrestricted ones have none
+    pub fn has_transitions(&self, badge_id: i64) -> bool {
+        self.badge_transitions.iter().any(|transition| transition.from_id == badge_id)
+    }
+
+    pub fn is_transition(&self, from_id: i64, to_id: i64) -> bool {
+        self.badge_transitions.iter().any(|transition| transition.from_id == from_id && transition.to_id == to_id)
+    }
+
+    // allows the transition if it wasn't before and the other way around
+    pub fn toggle_badge_transition(&mut self, from_id: i64, to_id: i64) -> Result<()> {
+        if from_id == to_id {
+            return Err("a badge always follows itself".into());
+        }
+        self.begin_operation("change badge transitions")?;
+
+        let existing = self.badge_transitions.iter()
+            .position(|transition| transition.from_id == from_id && transition.to_id == to_id);
+        match existing {
+            Some(idx) => {
+                self.storage.delete_badge_transition(self.badge_transitions[idx].id)?;
+                self.badge_transitions.remove(idx);
+            },
             None => {
-                let id: i64 = self.connection.prepare("INSERT INTO notes (text) VALUES ('') RETURNING ID")?
-                    .query_map([], |row| {
-                        row.get::<&str, i64>("id")
-                    })?
-                    .filter_map(|id| id.ok())
-                    .sum(); // TODO works but maybe there's a better way
+                let id = self.storage.insert_badge_transition(from_id, to_id)?;
+                self.badge_transitions.push(BadgeTransition { id, from_id, to_id });
+            }
+        }
+
+        Ok(())
+    }
+
+    // the badge with that name ignoring case that's done just when the task is, otherwise a new
+    // one is created so the task keeps its done state
+    fn badge_by_name(&mut self, name: &str, done: bool) -> Result<i64> {
+        if let Some(badge) = self.sorted_badges().into_iter().find(|badge| badge.name.eq_ignore_ascii_case(name) && badge.is_done() == done) {
+            return Ok(badge.id);
+        }
+
+        let color = Color::from_str(BADGE_COLORS[self.badges.len() % BADGE_COLORS.len()])?;
+        let badge_id = self.create_badge(name, color)?;
+        if done {
+            self.set_badge_done(badge_id, true)?;
+        }
+
+        Ok(badge_id)
+    }
 
-                self.connection.execute("UPDATE tasks SET note_id = ?1 WHERE sort_order = ?2", (id, idx_sort_order))?;
+    // puts the badge at `position` of the sorted badges, the others keep their relative order
+    pub fn move_badge(&mut self, badge_id: i64, position: usize) -> Result<()> {
+        let mut badge_ids: Vec<i64> = self.sorted_badges().iter().map(|badge| badge.id).collect();
+        let current_position = badge_ids.iter().position(|id| *id == badge_id).ok_or_else(|| "couldn't find badge".to_string())?;
+        badge_ids.remove(current_position);
+        badge_ids.insert(position.min(badge_ids.len()), badge_id);
 
-                let task = self.tasks.get_mut(idx_sort_order as usize).unwrap();
-                task.note_id = Some(id);
+        self.operation("reorder badges", |store| store.write_badge_order(&badge_ids))
+    }
+
+    // removes the badge, tasks of every list that carried it get `replacement_id` instead or no badge at all
+    pub fn delete_badge(&mut self, badge_id: i64, replacement_id: Option<i64>) -> Result<()> {
+        if replacement_id == Some(badge_id) {
+            return Err("a badge can't replace itself".into());
+        }
+        if replacement_id.is_some_and(|id| !self.badges.contains_key(&id)) {
+            return Err("the replacement badge doesn't exist".into());
+        }
+
+        // the tasks are done just when the replacement is
+        let replacement_done = replacement_id.and_then(|id| self.badges.get(&id)).is_some_and(Badge::is_done);
+        self.operation("delete badge", |store| {
+            store.update_badge_completed_at(badge_id, replacement_done)?;
+            store.storage.delete_badge(badge_id, replacement_id)?;
+
+            store.tasks.iter_mut()
+                .filter(|task| task.badge_id == Some(badge_id))
+                .for_each(|task| task.badge_id = replacement_id);
+            store.badges.remove(&badge_id);
+            store.badge_transitions.retain(|transition| transition.from_id != badge_id && transition.to_id != badge_id);
 
-                let note = Note::new(Some(id), String::new());
-                self.notes.insert(id, note);
+            let badge_ids: Vec<i64> = store.sorted_badges().iter().map(|badge| badge.id).collect();
+            store.write_badge_order(&badge_ids)
+        })
+    }
 
-                Ok(id)
+    fn write_badge_order(&mut self, badge_ids: &[i64]) -> Result<()> {
+        for (sort_order, badge_id) in badge_ids.iter().enumerate() {
+            if let Some(badge) = self.badges.get_mut(badge_id) {
+                badge.sort_order = sort_order as i64;
             }
         }
+
+        let badges: Vec<&Badge> = badge_ids.iter().filter_map(|badge_id| self.badges.get(badge_id)).collect();
+        self.storage.update_badges(&badges)
     }
 
-    pub fn delete_task(&mut self, idx_sort_order: i64) -> Result<()> {
-        self.connection.execute("DELETE FROM tasks WHERE sort_order = ?1", (idx_sort_order,))?;
-        self.connection.execute("UPDATE tasks SET sort_order = sort_order - 1 WHERE sort_order >= ?1", (idx_sort_order,))?;
-        self.tasks.remove(idx_sort_order as usize);
+    fn task_index(&self, task_id: i64) -> Result<usize> {
+        self.tasks.iter()
+            .position(|task| task.id == Some(task_id))
+            .ok_or_else(|| format!("couldn't find task {task_id}").into())
+    }
+
+    // sort order for a task inserted at `index`, the list gets spread out again once
+    // there's no gap left between the neighbours
+    fn sort_order_at(&mut self, index: usize) -> Result<i64> {
+        let previous = index.checked_sub(1)
+            .and_then(|idx| self.tasks.get(idx))
+            .map(|task| task.sort_order);
+        let next = self.tasks.get(index).map(|task| task.sort_order);
+
+        match (previous, next) {
+            (None, None) => Ok(0),
+            (Some(previous), None) => Ok(previous + SORT_ORDER_STEP),
+            (None, Some(next)) => Ok(next - SORT_ORDER_STEP),
+            (Some(previous), Some(next)) if next - previous > 1 => Ok(previous + (next - previous) / 2),
+            _ => {
+                self.renumber_tasks()?;
+                self.sort_order_at(index)
+            }
+        }
+    }
+
+    fn renumber_tasks(&mut self) -> Result<()> {
+        self.tasks.iter_mut()
+            .enumerate()
+            .for_each(|(idx, task)| task.sort_order = idx as i64 * SORT_ORDER_STEP);
+
+        let tasks: Vec<&Task> = self.tasks.iter().collect();
+        self.storage.update_tasks(&tasks)?;
+        self.tasks.iter_mut().for_each(|task| task.revision += 1);
 
         Ok(())
     }
 
-    pub fn update_task_text(&mut self, idx_sort_order: i64, text: &str) -> Result<()> {
-        self.connection.execute(r#"
-            UPDATE tasks
-                SET text = ?1
-            WHERE sort_order = ?2
-        "#, (text, idx_sort_order))?;
+    // writes the task at `idx` after it was changed in memory
+    fn store_task(&mut self, idx: usize) -> Result<()> {
+        let task = Task { updated_at: Some(dates::now()), ..self.tasks[idx].clone() };
+        self.storage.update_task(&task)?;
+        self.tasks[idx] = Task { revision: task.revision + 1, ..task };
 
-        // TODO update list
+        Ok(())
+    }
+
+    // writes the tasks with the given ids after they were changed in memory
+    fn store_tasks(&mut self, task_ids: &[i64]) -> Result<()> {
+        let is_changed = |task: &Task| task.id.is_some_and(|id| task_ids.contains(&id));
+        let now = dates::now();
+
+        let tasks: Vec<Task> = self.tasks.iter()
+            .filter(|task| is_changed(task))
+            .map(|task| Task { updated_at: Some(now), ..task.clone() })
+            .collect();
+        self.storage.update_tasks(&tasks.iter().collect::<Vec<_>>())?;
+        self.tasks.iter_mut()
+            .filter(|task| is_changed(task))
+            .for_each(|task| {
+                task.revision += 1;
+                task.updated_at = Some(now);
+            });
 
         Ok(())
     }
 
-    pub fn update_task_badge(&mut self, idx_sort_order: i64, badge_id: i64) -> Result<()> {
-        self.connection.execute(r#"
-            UPDATE tasks
-                SET badge_id = ?1
-            WHERE sort_order = ?2
-        "#, (badge_id, idx_sort_order))?;
+    pub fn create_task_at(&mut self, index: usize, task: Task) -> Result<i64> {
+        if index > self.tasks.len() {
+            return Err("couldn't access task".into());
+        }
+        // the list may get spread out before the insert
+        self.operation("create task", |store| store.insert_task_at(index, task))
+    }
+
+    // creates the task without starting an operation of its own
+    fn insert_task_at(&mut self, index: usize, mut task: Task) -> Result<i64> {
+        let now = Some(dates::now());
+        task.sort_order = self.sort_order_at(index)?;
+        // imported tasks bring their own creation and completion time
+        task.created_at = task.created_at.or(now);
+        task.updated_at = now;
+        task.completed_at = self.get_badge(&task).filter(|badge| badge.is_done()).and(task.completed_at.or(now));
+        let id = self.storage.insert_task(self.current_list_id, &task)?;
+        task.id = Some(id);
+        self.tasks.insert(index, task);
+
+        Ok(id)
+    }
+
+    pub fn create_task(&mut self, task: Task) -> Result<i64> {
+        self.create_task_at(self.tasks.len(), task)
+    }
+
+    pub fn get_or_create_note_id(&mut self, task_id: i64) -> Result<i64> {
+        let idx = self.task_index(task_id)?;
+        if let Some(note_id) = self.tasks[idx].note_id {
+            return Ok(note_id);
+        }
+
+        self.operation("create note", |store| {
+            let now = dates::now();
+            let note = Note { created_at: Some(now), updated_at: Some(now), ..Default::default() };
+            let id = store.storage.insert_note(&note)?;
+            store.notes.insert(id, Note { id: Some(id), ..note });
+            store.tasks[idx].note_id = Some(id);
+            store.store_task(idx)?;
+
+            Ok(id)
+        })
+    }
+
+    pub fn delete_task(&mut self, task_id: i64) -> Result<()> {
+        let idx = self.task_index(task_id)?;
+        let parent_id = self.tasks[idx].parent_id;
+
+        self.operation("delete task", |store| {
+            if store.running_timer().is_some_and(|entry| entry.task_id == task_id) {
+                store.stop_running_timer()?;
+            }
+
+            // subtasks move up one level and take the place of the deleted task
+            store.storage.trash_task(task_id, dates::now())?;
+
+            store.tasks.iter_mut()
+                .filter(|task| task.parent_id == Some(task_id))
+                .for_each(|task| task.parent_id = parent_id);
+            store.tasks.remove(idx);
+
+            Ok(())
+        })
+    }
+
+    // adds the tasks to the end of the current list through `create_task`, returns how many.
+    // The whole import is one operation, a single undo takes it back
+    pub fn import_tasks(&mut self, imported: Vec<ImportedTask>, mode: ImportMode) -> Result<usize> {
+        self.operation("import tasks", |store| {
+            if mode == ImportMode::Replace {
+                let task_ids: Vec<i64> = store.tasks.iter().rev().filter_map(|task| task.id).collect();
+                for task_id in task_ids {
+                    store.delete_task(task_id)?;
+                }
+            }
+
+            let count = imported.len();
+            let mut parent_ids: Vec<i64> = vec![];
+            for item in imported {
+                parent_ids.truncate(item.depth);
+
+                let badge_id = match (item.badge, item.done) {
+                    (Some(name), done) => Some(store.badge_by_name(&name, done)?),
+                    (None, true) => store.sorted_badges().iter().find(|badge| badge.is_done()).map(|badge| badge.id),
+                    (None, false) => None
+                };
+
+                let task = Task { badge_id, parent_id: parent_ids.last().copied(), ..item.task };
+                let id = store.create_task(task)?;
+                if let Some(text) = item.note {
+                    let note_id = store.get_or_create_note_id(id)?;
+                    store.update_note_text(note_id, &text)?;
+                }
+                parent_ids.push(id);
+            }
+
+            Ok(count)
+        })
+    }
+
+    // tasks in the trash of every list, most recently deleted first
+    pub fn trash(&self) -> Result<Vec<TrashedTask>> {
+        self.storage.load_trash()
+    }
+
+    // brings the task back to its list, which becomes the current one
+    pub fn restore_task(&mut self, task_id: i64) -> Result<()> {
+        let trashed = self.storage.load_trash()?
+            .into_iter()
+            .find(|trashed| trashed.task.id == Some(task_id))
+            .ok_or_else(|| format!("task {task_id} isn't in the trash"))?;
 
-        //self.fetch_data();
-        let task = self.tasks.get_mut(idx_sort_order as usize).expect("couldn't find task");
-        task.badge_id = Some(badge_id);
+        self.operation("restore task", |store| {
+            store.storage.restore_task(task_id)?;
+
+            store.switch_list(trashed.list_id)?;
+            store.place_restored_task(task_id)
+        })
+    }
+
+    // a restored task goes back to its old position as far as that's still possible: below
+    // its parent if that's still around, never in the middle of another subtree
+    fn place_restored_task(&mut self, task_id: i64) -> Result<()> {
+        let idx = self.task_index(task_id)?;
+        let mut task = self.tasks.remove(idx);
+
+        let parent_idx = task.parent_id
+            .and_then(|parent_id| self.tasks.iter().position(|other| other.id == Some(parent_id)));
+        let depths = self.task_depths();
+        let (start, end, depth) = match parent_idx {
+            Some(parent_idx) => (parent_idx + 1, self.subtree_end(parent_idx), depths[parent_idx] + 1),
+            None => (0, self.tasks.len(), 0)
+        };
+
+        let position = (idx.clamp(start, end)..end)
+            .find(|other_idx| depths[*other_idx] <= depth)
+            .unwrap_or(end);
+
+        let previous = position.checked_sub(1).and_then(|idx| self.tasks.get(idx)).map(|other| other.sort_order);
+        let next = self.tasks.get(position).map(|other| other.sort_order);
+        let fits = previous.is_none_or(|previous| previous < task.sort_order)
+            && next.is_none_or(|next| task.sort_order < next);
+
+        let parent_id = parent_idx.and(task.parent_id);
+        let moved = !fits || parent_id != task.parent_id;
+        task.parent_id = parent_id;
+        if !fits {
+            task.sort_order = self.sort_order_at(position)?;
+        }
+
+        self.tasks.insert(position, task);
+        if moved {
+            self.store_task(position)?;
+        }
 
         Ok(())
     }
 
-    pub fn unset_task_badge(&mut self, idx_sort_order: i64) -> Result<()> {
-        self.connection.execute(r#"
-            UPDATE tasks
-                SET badge_id = NULL
-            WHERE sort_order = ?1
-        "#, (idx_sort_order, ))?;
+    // deletes tasks in the trash for good, notes that no task refers to anymore go as well
+    pub fn purge_tasks(&mut self, task_ids: &[i64]) -> Result<()> {
+        self.operation("purge trash", |store| {
+            store.storage.purge_tasks(task_ids)?;
+
+            store.time_entries.retain(|entry| !task_ids.contains(&entry.task_id));
+            store.dependencies.retain(|dependency| !task_ids.contains(&dependency.task_id) && !task_ids.contains(&dependency.blocker_id));
+
+            store.notes = store.storage.load_notes()?
+                .into_iter()
+                .map(|note| (note.id.unwrap(), note))
+                .collect();
+
+            Ok(())
+        })
+    }
+
+    // orders the tasks of the current list by the key, subtasks are sorted among their
+    // siblings and stay below their parent
+    pub fn sort_tasks(&mut self, key: SortKey) -> Result<()> {
+        let mut children: HashMap<Option<i64>, Vec<usize>> = HashMap::new();
+        for (idx, task) in self.tasks.iter().enumerate() {
+            let parent_id = task.parent_id.filter(|parent_id| self.tasks.iter().any(|other| other.id == Some(*parent_id)));
+            children.entry(parent_id).or_default().push(idx);
+        }
+        for siblings in children.values_mut() {
+            siblings.sort_by(|a, b| key.compare(self, &self.tasks[*a], &self.tasks[*b]));
+        }
+
+        let mut order: Vec<usize> = Vec::with_capacity(self.tasks.len());
+        let mut pending: Vec<usize> = children.get(&None).cloned().unwrap_or_default();
+        pending.reverse();
+        while let Some(idx) = pending.pop() {
+            order.push(idx);
+            if let Some(subtasks) = children.get(&self.tasks[idx].id) {
+                pending.extend(subtasks.iter().rev());
+            }
+        }
+
+        self.operation("sort tasks", |store| {
+            let mut tasks: Vec<Option<Task>> = std::mem::take(&mut store.tasks).into_iter().map(Some).collect();
+            store.tasks = order.into_iter().filter_map(|idx| tasks[idx].take()).collect();
+            store.generation += 1;
 
-        let task = self.tasks.get_mut(idx_sort_order as usize).expect("couldn't find task");
-        task.badge_id = None;
+            store.renumber_tasks()
+        })
+    }
+
+    // there's at most one, it may belong to a task of another list
+    pub fn running_timer(&self) -> Option<&TimeEntry> {
+        self.time_entries.iter().find(|entry| entry.stopped_at.is_none())
+    }
+
+    pub fn tracked_time(&self, task_id: i64, now: NaiveDateTime) -> Duration {
+        self.time_entries.iter()
+            .filter(|entry| entry.task_id == task_id)
+            .map(|entry| entry.duration(now))
+            .fold(Duration::zero(), |total, duration| total + duration)
+    }
+
+    // a timer running on another task gets stopped first
+    pub fn start_timer(&mut self, task_id: i64) -> Result<()> {
+        self.task_index(task_id)?;
+        if self.running_timer().is_some_and(|entry| entry.task_id == task_id) {
+            return Err("the timer is running already".into());
+        }
+        self.operation("start timer", |store| {
+            if store.running_timer().is_some() {
+                store.stop_running_timer()?;
+            }
+
+            let mut entry = TimeEntry { id: None, task_id, started_at: dates::now(), stopped_at: None };
+            entry.id = Some(store.storage.insert_time_entry(&entry)?);
+            store.time_entries.push(entry);
+
+            Ok(())
+        })
+    }
+
+    // returns the time of the stopped entry
+    pub fn stop_timer(&mut self) -> Result<Duration> {
+        if self.running_timer().is_none() {
+            return Err("no timer is running".into());
+        }
+        self.begin_operation("stop timer")?;
+
+        self.stop_running_timer()
+    }
+
+    pub fn is_done(&self, task: &Task) -> bool {
+        self.get_badge(task).is_some_and(Badge::is_done)
+    }
+
+    // tasks of the current list the task waits for, in list order
+    pub fn blockers(&self, task_id: i64) -> Vec<&Task> {
+        self.tasks.iter()
+            .filter(|task| self.dependencies.iter().any(|dependency| dependency.task_id == task_id && task.id == Some(dependency.blocker_id)))
+            .collect()
+    }
+
+    // tasks of the current list waiting for the task, in list order
+    pub fn blocked_tasks(&self, task_id: i64) -> Vec<&Task> {
+        self.tasks.iter()
+            .filter(|task| self.dependencies.iter().any(|dependency| dependency.blocker_id == task_id && task.id == Some(dependency.task_id)))
+            .collect()
+    }
+
+    // blockers in the trash don't count until they're restored
+    pub fn is_blocked(&self, task: &Task) -> bool {
+        task.id.is_some_and(|task_id| self.blockers(task_id).iter().any(|blocker| !self.is_done(blocker)))
+    }
+
+    pub fn add_blocker(&mut self, task_id: i64, blocker_id: i64) -> Result<()> {
+        self.task_index(task_id)?;
+        self.task_index(blocker_id)?;
+        if task_id == blocker_id {
+            return Err("a task can't block itself".into());
+        }
+        if self.dependencies.iter().any(|dependency| dependency.task_id == task_id && dependency.blocker_id == blocker_id) {
+            return Err("the task is blocked by that one already".into());
+        }
+        if self.depends_on(blocker_id, task_id) {
+            return Err("that would be a cycle, the blocker waits for the task already".into());
+        }
+        self.begin_operation("add blocker")?;
+
+        let id = self.storage.insert_dependency(task_id, blocker_id)?;
+        self.dependencies.push(Dependency { id, task_id, blocker_id });
 
         Ok(())
     }
 
-    pub fn _update_task_order(&mut self, task: &Task, sort_order: i64) -> Result<()> {
-        self.connection.execute(r#"
-            UPDATE tasks
-                SET sort_order = ?1
-            WHERE id = ?2
-        "#, (sort_order, task.id))?;
+    pub fn remove_blocker(&mut self, task_id: i64, blocker_id: i64) -> Result<()> {
+        let idx = self.dependencies.iter()
+            .position(|dependency| dependency.task_id == task_id && dependency.blocker_id == blocker_id)
+            .ok_or("the task isn't blocked by that one")?;
+        self.begin_operation("remove blocker")?;
 
-        // TODO update list
+        self.storage.delete_dependency(self.dependencies[idx].id)?;
+        self.dependencies.remove(idx);
 
         Ok(())
     }
 
-    pub fn update_note_text(&mut self, note_idx: i64, text: &str) -> Result<()> {
-        self.connection.execute(r#"
-            UPDATE notes
-                SET text = ?1
-            WHERE id = ?2
-        "#, (text, note_idx))?;
+    // whether `task_id` waits for `other_id`, directly or through other blockers
+    fn depends_on(&self, task_id: i64, other_id: i64) -> bool {
+        let mut visited = vec![task_id];
+        let mut pending = vec![task_id];
+        while let Some(id) = pending.pop() {
+            for dependency in self.dependencies.iter().filter(|dependency| dependency.task_id == id) {
+                if dependency.blocker_id == other_id {
+                    return true;
+                }
+                if !visited.contains(&dependency.blocker_id) {
+                    visited.push(dependency.blocker_id);
+                    pending.push(dependency.blocker_id);
+                }
+            }
+        }
+
+        false
+    }
+
+    fn stop_running_timer(&mut self) -> Result<Duration> {
+        let now = dates::now();
+        let entry = self.time_entries.iter_mut()
+            .find(|entry| entry.stopped_at.is_none())
+            .ok_or("no timer is running")?;
+
+        entry.stopped_at = Some(now);
+        self.storage.update_time_entry(entry)?;
+
+        Ok(entry.duration(now))
+    }
+
+    pub fn indent_task(&mut self, task_id: i64) -> Result<()> {
+        let idx = self.task_index(task_id)?;
+        let parent_id = self.tasks[idx].parent_id;
+
+        // the closest sibling above becomes the new parent, the subtree moves along since
+        // its children still point to this task
+        let sibling_id = self.tasks[..idx].iter()
+            .rev()
+            .take_while(|other| parent_id.is_none() || other.id != parent_id)
+            .find(|other| other.parent_id == parent_id)
+            .and_then(|sibling| sibling.id);
+
+        let Some(sibling_id) = sibling_id else {
+            return Ok(())
+        };
+
+        self.begin_operation("indent task")?;
+
+        self.tasks[idx].parent_id = Some(sibling_id);
+        self.store_task(idx)
+    }
+
+    pub fn outdent_task(&mut self, task_id: i64) -> Result<()> {
+        let idx = self.task_index(task_id)?;
+        let Some(parent_id) = self.tasks[idx].parent_id else {
+            return Ok(())
+        };
+
+        let grandparent_id = self.tasks.iter()
+            .find(|other| other.id == Some(parent_id))
+            .and_then(|parent| parent.parent_id);
+
+        self.operation("outdent task", |store| {
+            // siblings below the task are adopted by it, that way every task stays where it is
+            // on screen and only moves one level to the left
+            store.tasks.iter_mut()
+                .skip(idx + 1)
+                .filter(|other| other.parent_id == Some(parent_id))
+                .for_each(|other| other.parent_id = Some(task_id));
+            store.tasks[idx].parent_id = grandparent_id;
 
-        let note = self.notes.get_mut(&note_idx).unwrap();
-        note.text = String::from(text);
+            let changed: Vec<i64> = store.tasks[idx..].iter()
+                .filter(|other| other.id == Some(task_id) || other.parent_id == Some(task_id))
+                .filter_map(|other| other.id)
+                .collect();
+            store.store_tasks(&changed)
+        })
+    }
+
+    // fails with `Conflict` if someone else changed the task since it was loaded
+    pub fn update_task_text(&mut self, task_id: i64, text: &str) -> Result<()> {
+        let idx = self.task_index(task_id)?;
+        self.begin_operation("edit task")?;
+
+        self.tasks[idx].text = String::from(text);
+        self.store_task(idx)
+    }
+
+    // completing a recurring task creates its next occurrence, returns the id of that one
+    pub fn update_task_badge(&mut self, task_id: i64, badge_id: i64) -> Result<Option<i64>> {
+        let idx = self.task_index(task_id)?;
+        let from_id = self.tasks[idx].badge_id;
+        if !self.can_change_badge(from_id, badge_id) {
+            let name = |id: Option<i64>| id.and_then(|id| self.badges.get(&id)).map(|badge| badge.name.clone()).unwrap_or_default();
+            return Err(format!("{} can't be followed by {}", name(from_id), name(Some(badge_id))).into());
+        }
+        self.operation("set badge", |store| {
+            let was_done = store.get_badge(&store.tasks[idx]).is_some_and(Badge::is_done);
+            store.tasks[idx].badge_id = Some(badge_id);
+            store.update_completed_at(idx);
+
+            let completed = !was_done && store.tasks[idx].completed_at.is_some();
+            let recurrence = match completed {
+                true => store.tasks[idx].recurrence.take(),
+                false => None
+            };
+            store.store_task(idx)?;
+
+            recurrence
+                .map(|recurrence| store.create_next_occurrence(idx, recurrence))
+                .transpose()
+        })
+    }
+
+    // the next occurrence goes right below the completed one, it shares the note and starts
+    // over with the first badge that isn't done
+    fn create_next_occurrence(&mut self, idx: usize, recurrence: Recurrence) -> Result<i64> {
+        let task = &self.tasks[idx];
+        let recurrence = recurrence.pinned(task.due_date);
+        let due_date = recurrence.next_due_date(task.due_date, dates::today());
+
+        // the start date keeps its distance to the due date
+        let start_date = match (task.start_date, task.due_date, due_date) {
+            (Some(start_date), Some(old_due_date), Some(due_date)) => start_date.checked_add_signed(due_date - old_due_date),
+            _ => None
+        };
+
+        let next = Task {
+            text: task.text.clone(),
+            note_id: task.note_id,
+            parent_id: task.parent_id,
+            due_date,
+            start_date,
+            recurrence: Some(recurrence),
+            badge_id: self.sorted_badges().iter().find(|badge| !badge.is_done()).map(|badge| badge.id),
+            ..Default::default()
+        };
+
+        let index = self.subtree_end(idx);
+        self.insert_task_at(index, next)
+    }
+
+    // moves the task on to the next badge it may get, in badge order and starting over after
+    // the last one
+    pub fn cycle_task_badge(&mut self, task_id: i64) -> Result<Option<i64>> {
+        let idx = self.task_index(task_id)?;
+        let badge_ids: Vec<i64> = self.sorted_badges().iter().map(|badge| badge.id).collect();
+
+        let next_id = match self.tasks[idx].badge_id {
+            Some(badge_id) => {
+                let next_ids = self.next_badge_ids(badge_id);
+                let position = badge_ids.iter().position(|id| *id == badge_id).unwrap_or(0);
+
+                badge_ids[position + 1..].iter()
+                    .chain(&badge_ids[..position])
+                    .find(|id| next_ids.contains(id))
+                    .copied()
+            },
+            None => badge_ids.first().copied()
+        };
+
+        let next_id = next_id.ok_or("there's no badge to move on to")?;
+        self.update_task_badge(task_id, next_id)
+    }
+
+    pub fn update_task_recurrence(&mut self, task_id: i64, recurrence: Option<Recurrence>) -> Result<()> {
+        let idx = self.task_index(task_id)?;
+        self.begin_operation("set recurrence")?;
+
+        self.tasks[idx].recurrence = recurrence;
+        self.store_task(idx)
+    }
+
+    // set when a task gets a done badge, kept while it moves between done badges
+    fn update_completed_at(&mut self, idx: usize) {
+        let done = self.get_badge(&self.tasks[idx]).is_some_and(|badge| badge.is_done());
+        let task = &mut self.tasks[idx];
+        task.completed_at = match (done, task.completed_at) {
+            (true, Some(completed_at)) => Some(completed_at),
+            (true, None) => Some(dates::now()),
+            (false, _) => None
+        };
+    }
+
+    pub fn unset_task_badge(&mut self, task_id: i64) -> Result<()> {
+        let idx = self.task_index(task_id)?;
+        self.begin_operation("remove badge")?;
+
+        self.tasks[idx].badge_id = None;
+        self.update_completed_at(idx);
+        self.store_task(idx)
+    }
+
+    pub fn update_task_due_date(&mut self, task_id: i64, due_date: Option<NaiveDate>) -> Result<()> {
+        let idx = self.task_index(task_id)?;
+        self.begin_operation("set due date")?;
+
+        self.tasks[idx].due_date = due_date;
+        self.store_task(idx)
+    }
+
+    pub fn update_task_start_date(&mut self, task_id: i64, start_date: Option<NaiveDate>) -> Result<()> {
+        let idx = self.task_index(task_id)?;
+        self.begin_operation("set start date")?;
+
+        self.tasks[idx].start_date = start_date;
+        self.store_task(idx)
+    }
+
+    // fails with `Conflict` if someone else changed the note since it was loaded
+    pub fn update_note_text(&mut self, note_id: i64, text: &str) -> Result<()> {
+        let note = self.notes.get(&note_id).ok_or_else(|| format!("couldn't find note {note_id}"))?;
+        let changed = Note { text: String::from(text), updated_at: Some(dates::now()), ..note.clone() };
+
+        self.begin_operation("edit note")?;
+        self.storage.update_note(&changed)?;
+
+        self.notes.insert(note_id, Note { revision: changed.revision + 1, ..changed });
 
         Ok(())
     }
 
+    pub fn location(&self) -> String {
+        self.storage.location()
+    }
+
+    // searches the tasks of all lists, best hits first
+    pub fn search(&self, input: &str, limit: usize) -> Result<Vec<SearchHit>> {
+        self.storage.search(input, limit)
+    }
+
     pub fn get_badge(&self, task: &Task) -> Option<&Badge> {
         let badge_id = &task.badge_id?;
         self.badges.get(badge_id)
@@ -316,5 +1381,49 @@ impl TaskStore {
         self.notes.get(&note_id)
     }
 
+    pub fn task_depths(&self) -> Vec<usize> {
+        let mut depth_by_id: HashMap<i64, usize> = HashMap::new();
+        self.tasks.iter()
+            .map(|task| {
+                let depth = task.parent_id
+                    .and_then(|parent_id| depth_by_id.get(&parent_id))
+                    .map(|depth| depth + 1)
+                    .unwrap_or(0);
+
+                if let Some(task_id) = task.id {
+                    depth_by_id.insert(task_id, depth);
+                }
+
+                depth
+            })
+            .collect()
+    }
+
+    pub fn has_subtasks(&self, idx_sort_order: usize) -> bool {
+        self.subtree_end(idx_sort_order) > idx_sort_order + 1
+    }
+
+    // index right after the last descendant of the task
+    pub fn subtree_end(&self, idx_sort_order: usize) -> usize {
+        let depths = self.task_depths();
+        let Some(depth) = depths.get(idx_sort_order) else {
+            return self.tasks.len()
+        };
+
+        (idx_sort_order + 1..self.tasks.len())
+            .find(|idx| depths[*idx] <= *depth)
+            .unwrap_or(self.tasks.len())
+    }
+
 }
 
+// badge colors are stored as `#RRGGBB`
+fn color_to_string(color: Color) -> Result<String> {
+    match color {
+        Color::Rgb(r, g, b) => Ok(format!("#{r:02X}{g:02X}{b:02X}")),
+        _ => Err("only rgb colors can be stored".into())
+    }
+}
+
+
+
@@ -0,0 +1,122 @@
+use rusqlite::Connection;
+
+use super::{Note, Result, Task};
+
+// wrapped around the matched words of `SearchHit::text` and `SearchHit::snippet`
+pub const MATCH_START: char = '\u{2}';
+pub const MATCH_END: char = '\u{3}';
+
+pub struct SearchHit {
+    pub task_id: i64,
+    pub list_id: i64,
+    pub text: String,
+    // part of the note around the match, empty if the note didn't match
+    pub snippet: String
+}
+
+// every word of the input has to match as a prefix, fts5 syntax isn't exposed so
+// nothing the user types can end up as a malformed query
+fn to_match_query(input: &str) -> String {
+    input.split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn search(connection: &Connection, input: &str, limit: usize) -> Result<Vec<SearchHit>> {
+    let query = to_match_query(input);
+    if query.is_empty() {
+        return Ok(vec![]);
+    }
+
+    // matches in the task text weigh more than matches in the note
+    let hits = connection.prepare(r#"
+        SELECT
+            tasks.id,
+            tasks.list_id,
+            highlight(task_search, 0, ?2, ?3),
+            snippet(task_search, 1, ?2, ?3, '…', 12)
+        FROM task_search
+            JOIN tasks ON tasks.id = task_search.rowid
+        WHERE task_search MATCH ?1 AND tasks.deleted_at IS NULL
+        ORDER BY bm25(task_search, 4.0, 1.0)
+        LIMIT ?4
+    "#)?
+        .query_map((&query, MATCH_START.to_string(), MATCH_END.to_string(), limit as i64), |row| {
+            // without a match in the note the snippet is just its beginning
+            let snippet: String = row.get(3)?;
+            let snippet = if snippet.contains(MATCH_START) { snippet } else { String::new() };
+
+            Ok(SearchHit {
+                task_id: row.get(0)?,
+                list_id: row.get(1)?,
+                text: row.get(2)?,
+                snippet
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+
+    Ok(hits)
+}
+
+// the same kind of search for storages without an index: every word of the input has to
+// be part of the task text or its note, ignoring case, hits keep the order of `tasks`
+pub fn scan<'a>(tasks: impl Iterator<Item = (i64, &'a Task)>, notes: &[Note], input: &str, limit: usize) -> Vec<SearchHit> {
+    let words: Vec<String> = input.split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .map(|word| word.to_lowercase())
+        .collect();
+    if words.is_empty() {
+        return vec![];
+    }
+
+    tasks
+        .filter_map(|(list_id, task)| {
+            let note = task.note_id
+                .and_then(|note_id| notes.iter().find(|note| note.id == Some(note_id)))
+                .map(|note| note.text.as_str())
+                .unwrap_or_default();
+
+            let haystack = format!("{}\n{}", task.text, note).to_lowercase();
+            if !words.iter().all(|word| haystack.contains(word.as_str())) {
+                return None;
+            }
+
+            let snippet = highlight(note, &words);
+            Some(SearchHit {
+                task_id: task.id?,
+                list_id,
+                text: highlight(&task.text, &words),
+                snippet: if snippet.contains(MATCH_START) { snippet } else { String::new() }
+            })
+        })
+        .take(limit)
+        .collect()
+}
+
+// wraps every occurrence of the (lowercase) words in MATCH_START and MATCH_END
+fn highlight(text: &str, words: &[String]) -> String {
+    let mut highlighted = String::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let matched = words.iter().find(|word| {
+            rest.get(..word.len()).is_some_and(|start| start.to_lowercase() == **word)
+        });
+
+        match matched {
+            Some(word) => {
+                highlighted.push(MATCH_START);
+                highlighted.push_str(&rest[..word.len()]);
+                highlighted.push(MATCH_END);
+                rest = &rest[word.len()..];
+            },
+            None => {
+                highlighted.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+
+    highlighted
+}
@@ -0,0 +1,449 @@
+use ratatui::style::Color;
+use std::path::Path;
+use chrono::NaiveDateTime;
+
+use rusqlite::Connection;
+
+use crate::{cli, dates::Recurrence};
+
+use super::{color_to_string, history, Conflict, migrations, search, storage::Storage, Badge, BadgeTransition, Note, Result, SearchHit, Task, TaskList, TimeEntry, TrashedTask, Dependency, SORT_ORDER_STEP};
+
+pub struct SqliteStorage {
+    connection: Connection,
+    // bumped by sqlite whenever another connection commits to the database
+    data_version: i64
+}
+
+fn data_version(connection: &Connection) -> Result<i64> {
+    Ok(connection.query_row("PRAGMA data_version", [], |row| row.get(0))?)
+}
+
+// an update that matched no row found another revision, or no row at all
+fn expect_one_row(changed: usize) -> Result<()> {
+    match changed {
+        0 => Err(Box::new(Conflict)),
+        _ => Ok(())
+    }
+}
+
+impl SqliteStorage {
+    // brings the schema up to date before anything else touches the database
+    pub fn open(mut connection: Connection) -> Result<Self> {
+        migrations::migrate(&mut connection)?;
+        history::install(&connection)?;
+        // off by default in sqlite, has to be switched on for every connection
+        connection.pragma_update(None, "foreign_keys", true)?;
+
+        let data_version = data_version(&connection)?;
+        Ok(Self { connection, data_version })
+    }
+}
+
+// calls writing several statements use savepoints, unlike transactions those also work
+// within one started by `begin_transaction`
+impl Storage for SqliteStorage {
+    fn location(&self) -> String {
+        match self.connection.path() {
+            Some(path) if !path.is_empty() => cli::display_path(Path::new(path)),
+            _ => "in memory".to_string()
+        }
+    }
+
+    fn load_lists(&self) -> Result<Vec<TaskList>> {
+        let lists = self.connection.prepare("SELECT * FROM lists ORDER BY sort_order")?
+            .query_map([], |row| {
+                TaskList::from_row(row)
+                    .map_err(|_| rusqlite::Error::ExecuteReturnedResults)
+            })?
+            .filter_map(|list| list.ok())
+            .collect();
+
+        Ok(lists)
+    }
+
+    fn load_badges(&self) -> Result<Vec<Badge>> {
+        let badges = self.connection.prepare("SELECT * FROM badges")?
+            .query_map([], |row| {
+                Badge::from_row(row)
+                    .map_err(|_| rusqlite::Error::ExecuteReturnedResults)
+            })?
+            .filter_map(|badge| badge.ok())
+            .collect();
+
+        Ok(badges)
+    }
+
+    fn load_notes(&self) -> Result<Vec<Note>> {
+        let notes = self.connection.prepare("SELECT * FROM notes")?
+            .query_map([], |row| {
+                Note::from_row(row)
+                    .map_err(|_| rusqlite::Error::ExecuteReturnedResults)
+            })?
+            .filter_map(|note| note.ok())
+            .collect();
+
+        Ok(notes)
+    }
+
+    fn load_tasks(&self, list_id: i64) -> Result<Vec<Task>> {
+        let tasks = self.connection.prepare("SELECT * FROM tasks WHERE list_id = ?1 AND deleted_at IS NULL ORDER BY sort_order")?
+            .query_map((list_id,), |row| {
+                Task::from_row(row)
+                    .map_err(|_| rusqlite::Error::ExecuteReturnedResults)
+            })?
+            .filter_map(|task| task.ok())
+            .collect();
+
+        Ok(tasks)
+    }
+
+    fn insert_list(&mut self, name: &str) -> Result<i64> {
+        let id = self.connection.query_row(r#"
+            INSERT INTO lists (name, sort_order)
+                VALUES (?1, (SELECT coalesce(max(sort_order) + 1, 0) FROM lists))
+            RETURNING id
+        "#, (name,), |row| row.get("id"))?;
+
+        Ok(id)
+    }
+
+    fn rename_list(&mut self, list_id: i64, name: &str) -> Result<()> {
+        self.connection.execute("UPDATE lists SET name = ?1 WHERE id = ?2", (name, list_id))?;
+        Ok(())
+    }
+
+    fn delete_list(&mut self, list_id: i64) -> Result<()> {
+        let transaction = self.connection.savepoint()?;
+        let note_ids: Vec<i64> = transaction.prepare("SELECT note_id FROM tasks WHERE list_id = ?1 AND note_id IS NOT NULL")?
+            .query_map((list_id,), |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        transaction.execute("DELETE FROM time_entries WHERE task_id IN (SELECT id FROM tasks WHERE list_id = ?1)", (list_id,))?;
+        transaction.execute(r#"
+            DELETE FROM task_dependencies
+            WHERE task_id IN (SELECT id FROM tasks WHERE list_id = ?1) OR blocker_id IN (SELECT id FROM tasks WHERE list_id = ?1)
+        "#, (list_id,))?;
+        transaction.execute("DELETE FROM tasks WHERE list_id = ?1", (list_id,))?;
+        // next occurrences of a recurring task share the note and may live in another list
+        for note_id in &note_ids {
+            transaction.execute(r#"
+                DELETE FROM notes
+                WHERE id = ?1 AND id NOT IN (SELECT note_id FROM tasks WHERE note_id IS NOT NULL)
+            "#, (note_id,))?;
+        }
+        transaction.execute("DELETE FROM lists WHERE id = ?1", (list_id,))?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    fn insert_badge(&mut self, name: &str, color: Color) -> Result<Badge> {
+        let (id, sort_order) = self.connection.query_row(r#"
+            INSERT INTO badges (name, color, sort_order)
+                VALUES (?1, ?2, (SELECT coalesce(max(sort_order) + 1, 0) FROM badges))
+            RETURNING id, sort_order
+        "#, (name, color_to_string(color)?), |row| Ok((row.get("id")?, row.get("sort_order")?)))?;
+
+        Ok(Badge { id, name: String::from(name), color, sort_order, done: false })
+    }
+
+    fn update_badge(&mut self, badge: &Badge) -> Result<()> {
+        self.connection.execute(r#"
+            UPDATE badges
+                SET name = ?1, color = ?2, sort_order = ?3, done = ?4
+            WHERE id = ?5
+        "#, (&badge.name, color_to_string(badge.color)?, badge.sort_order, badge.done, badge.id))?;
+
+        Ok(())
+    }
+
+    fn update_badges(&mut self, badges: &[&Badge]) -> Result<()> {
+        let transaction = self.connection.savepoint()?;
+        for badge in badges {
+            transaction.execute(r#"
+                UPDATE badges
+                    SET name = ?1, color = ?2, sort_order = ?3, done = ?4
+                WHERE id = ?5
+            "#, (&badge.name, color_to_string(badge.color)?, badge.sort_order, badge.done, badge.id))?;
+        }
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    fn update_badge_completed_at(&mut self, badge_id: i64, completed_at: Option<NaiveDateTime>) -> Result<()> {
+        self.connection.execute(r#"
+            UPDATE tasks
+                SET completed_at = ?1, revision = revision + 1
+            WHERE badge_id = ?2 AND (completed_at IS NULL) = (?1 IS NOT NULL)
+        "#, (completed_at, badge_id))?;
+
+        Ok(())
+    }
+
+    fn delete_badge(&mut self, badge_id: i64, replacement_id: Option<i64>) -> Result<()> {
+        let transaction = self.connection.savepoint()?;
+        transaction.execute("UPDATE tasks SET badge_id = ?1 WHERE badge_id = ?2", (replacement_id, badge_id))?;
+        transaction.execute("DELETE FROM badge_transitions WHERE ?1 IN (from_id, to_id)", (badge_id,))?;
+        transaction.execute("DELETE FROM badges WHERE id = ?1", (badge_id,))?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    fn load_badge_transitions(&self) -> Result<Vec<BadgeTransition>> {
+        let transitions = self.connection.prepare("SELECT * FROM badge_transitions")?
+            .query_map([], |row| {
+                BadgeTransition::from_row(row)
+                    .map_err(|_| rusqlite::Error::ExecuteReturnedResults)
+            })?
+            .filter_map(|transition| transition.ok())
+            .collect();
+
+        Ok(transitions)
+    }
+
+    fn insert_badge_transition(&mut self, from_id: i64, to_id: i64) -> Result<i64> {
+        let id = self.connection.query_row(r#"
+            INSERT INTO badge_transitions (from_id, to_id)
+                VALUES (?1, ?2)
+            RETURNING id
+        "#, (from_id, to_id), |row| row.get("id"))?;
+
+        Ok(id)
+    }
+
+    fn delete_badge_transition(&mut self, transition_id: i64) -> Result<()> {
+        self.connection.execute("DELETE FROM badge_transitions WHERE id = ?1", (transition_id,))?;
+        Ok(())
+    }
+
+    fn insert_note(&mut self, note: &Note) -> Result<i64> {
+        let id = self.connection.query_row(r#"
+            INSERT INTO notes (text, created_at, updated_at)
+                VALUES (?1, ?2, ?3)
+            RETURNING id
+        "#, (&note.text, note.created_at, note.updated_at), |row| row.get("id"))?;
+
+        Ok(id)
+    }
+
+    fn update_note(&mut self, note: &Note) -> Result<()> {
+        let changed = self.connection.execute(r#"
+            UPDATE notes
+                SET text = ?1, updated_at = ?2, revision = revision + 1
+            WHERE id = ?3 AND revision = ?4
+        "#, (&note.text, note.updated_at, note.id, note.revision))?;
+
+        expect_one_row(changed)
+    }
+
+    fn insert_task(&mut self, list_id: i64, task: &Task) -> Result<i64> {
+        let id = self.connection.query_row(r#"
+            INSERT INTO tasks (text, badge_id, note_id, parent_id, due_date, start_date, list_id, sort_order, created_at, updated_at, completed_at, recurrence)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+            RETURNING id
+        "#, (&task.text, task.badge_id, task.note_id, task.parent_id, task.due_date, task.start_date, list_id, task.sort_order, task.created_at, task.updated_at, task.completed_at, task.recurrence.as_ref().map(Recurrence::to_string)), |row| row.get("id"))?;
+
+        Ok(id)
+    }
+
+    fn update_task(&mut self, task: &Task) -> Result<()> {
+        let changed = self.connection.execute(r#"
+            UPDATE tasks
+                SET text = ?1, badge_id = ?2, note_id = ?3, parent_id = ?4, due_date = ?5, start_date = ?6, sort_order = ?7, updated_at = ?10, completed_at = ?11, recurrence = ?12, revision = revision + 1
+            WHERE id = ?8 AND revision = ?9
+        "#, (&task.text, task.badge_id, task.note_id, task.parent_id, task.due_date, task.start_date, task.sort_order, task.id, task.revision, task.updated_at, task.completed_at, task.recurrence.as_ref().map(Recurrence::to_string)))?;
+
+        expect_one_row(changed)
+    }
+
+    fn update_tasks(&mut self, tasks: &[&Task]) -> Result<()> {
+        let transaction = self.connection.savepoint()?;
+        for task in tasks {
+            let changed = transaction.execute(r#"
+                UPDATE tasks
+                    SET text = ?1, badge_id = ?2, note_id = ?3, parent_id = ?4, due_date = ?5, start_date = ?6, sort_order = ?7, updated_at = ?10, completed_at = ?11, recurrence = ?12, revision = revision + 1
+                WHERE id = ?8 AND revision = ?9
+            "#, (&task.text, task.badge_id, task.note_id, task.parent_id, task.due_date, task.start_date, task.sort_order, task.id, task.revision, task.updated_at, task.completed_at, task.recurrence.as_ref().map(Recurrence::to_string)))?;
+            expect_one_row(changed)?;
+        }
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    fn trash_task(&mut self, task_id: i64, deleted_at: NaiveDateTime) -> Result<()> {
+        let transaction = self.connection.savepoint()?;
+        transaction.execute(r#"
+            UPDATE tasks
+                SET parent_id = (SELECT parent_id FROM tasks WHERE id = ?1)
+            WHERE parent_id = ?1 AND deleted_at IS NULL
+        "#, (task_id,))?;
+        transaction.execute("UPDATE tasks SET deleted_at = ?2 WHERE id = ?1", (task_id, deleted_at))?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    fn load_trash(&self) -> Result<Vec<TrashedTask>> {
+        let trash = self.connection.prepare("SELECT * FROM tasks WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC, id DESC")?
+            .query_map([], |row| {
+                TrashedTask::from_row(row)
+                    .map_err(|_| rusqlite::Error::ExecuteReturnedResults)
+            })?
+            .filter_map(|trashed| trashed.ok())
+            .collect();
+
+        Ok(trash)
+    }
+
+    fn restore_task(&mut self, task_id: i64) -> Result<()> {
+        self.connection.execute("UPDATE tasks SET deleted_at = NULL WHERE id = ?1", (task_id,))?;
+        Ok(())
+    }
+
+    fn purge_tasks(&mut self, task_ids: &[i64]) -> Result<()> {
+        let transaction = self.connection.savepoint()?;
+        for task_id in task_ids {
+            transaction.execute("DELETE FROM time_entries WHERE task_id = (SELECT id FROM tasks WHERE id = ?1 AND deleted_at IS NOT NULL)", (task_id,))?;
+            transaction.execute(r#"
+                DELETE FROM task_dependencies
+                WHERE ?1 IN (task_id, blocker_id) AND ?1 IN (SELECT id FROM tasks WHERE deleted_at IS NOT NULL)
+            "#, (task_id,))?;
+            // subtasks that were trashed along with it would point nowhere
+            transaction.execute(r#"
+                UPDATE tasks
+                    SET parent_id = (SELECT parent_id FROM tasks WHERE id = ?1)
+                WHERE parent_id = ?1 AND ?1 IN (SELECT id FROM tasks WHERE deleted_at IS NOT NULL)
+            "#, (task_id,))?;
+            transaction.execute("DELETE FROM tasks WHERE id = ?1 AND deleted_at IS NOT NULL", (task_id,))?;
+        }
+        transaction.execute(r#"
+            DELETE FROM notes
+            WHERE id NOT IN (SELECT note_id FROM tasks WHERE note_id IS NOT NULL)
+        "#, ())?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    fn move_tasks_to_list(&mut self, task_ids: &[i64], list_id: i64) -> Result<()> {
+        let transaction = self.connection.savepoint()?;
+        let last_sort_order: Option<i64> = transaction.query_row("SELECT max(sort_order) FROM tasks WHERE list_id = ?1", (list_id,), |row| row.get(0))?;
+        let first_sort_order = last_sort_order.map(|sort_order| sort_order + SORT_ORDER_STEP).unwrap_or(0);
+        for (offset, task_id) in task_ids.iter().enumerate() {
+            transaction.execute(r#"
+                UPDATE tasks
+                    SET list_id = ?1, sort_order = ?2
+                WHERE id = ?3
+            "#, (list_id, first_sort_order + offset as i64 * SORT_ORDER_STEP, task_id))?;
+        }
+        if let Some(root_id) = task_ids.first() {
+            transaction.execute("UPDATE tasks SET parent_id = NULL WHERE id = ?1", (root_id,))?;
+        }
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    fn load_time_entries(&self) -> Result<Vec<TimeEntry>> {
+        let entries = self.connection.prepare("SELECT * FROM time_entries ORDER BY started_at, id")?
+            .query_map([], |row| {
+                TimeEntry::from_row(row)
+                    .map_err(|_| rusqlite::Error::ExecuteReturnedResults)
+            })?
+            .filter_map(|entry| entry.ok())
+            .collect();
+
+        Ok(entries)
+    }
+
+    fn insert_time_entry(&mut self, entry: &TimeEntry) -> Result<i64> {
+        let id = self.connection.query_row(r#"
+            INSERT INTO time_entries (task_id, started_at, stopped_at)
+                VALUES (?1, ?2, ?3)
+            RETURNING id
+        "#, (entry.task_id, entry.started_at, entry.stopped_at), |row| row.get("id"))?;
+
+        Ok(id)
+    }
+
+    fn update_time_entry(&mut self, entry: &TimeEntry) -> Result<()> {
+        let id = entry.id.ok_or("time entry has no id")?;
+        let changed = self.connection.execute(r#"
+            UPDATE time_entries
+                SET task_id = ?1, started_at = ?2, stopped_at = ?3
+            WHERE id = ?4
+        "#, (entry.task_id, entry.started_at, entry.stopped_at, id))?;
+
+        expect_one_row(changed)
+    }
+
+    fn load_dependencies(&self) -> Result<Vec<Dependency>> {
+        let dependencies = self.connection.prepare("SELECT * FROM task_dependencies")?
+            .query_map([], |row| {
+                Dependency::from_row(row)
+                    .map_err(|_| rusqlite::Error::ExecuteReturnedResults)
+            })?
+            .filter_map(|dependency| dependency.ok())
+            .collect();
+
+        Ok(dependencies)
+    }
+
+    fn insert_dependency(&mut self, task_id: i64, blocker_id: i64) -> Result<i64> {
+        let id = self.connection.query_row(r#"
+            INSERT INTO task_dependencies (task_id, blocker_id)
+                VALUES (?1, ?2)
+            RETURNING id
+        "#, (task_id, blocker_id), |row| row.get("id"))?;
+
+        Ok(id)
+    }
+
+    fn delete_dependency(&mut self, dependency_id: i64) -> Result<()> {
+        self.connection.execute("DELETE FROM task_dependencies WHERE id = ?1", (dependency_id,))?;
+        Ok(())
+    }
+
+    // immediate so a concurrent writer makes us wait right at the start instead of failing
+    // halfway through
+    fn begin_transaction(&mut self) -> Result<()> {
+        self.connection.execute_batch("BEGIN IMMEDIATE")?;
+        Ok(())
+    }
+
+    fn commit_transaction(&mut self) -> Result<()> {
+        self.connection.execute_batch("COMMIT")?;
+        Ok(())
+    }
+
+    fn rollback_transaction(&mut self) -> Result<()> {
+        self.connection.execute_batch("ROLLBACK")?;
+        Ok(())
+    }
+
+    fn begin_operation(&mut self, name: &str) -> Result<()> {
+        history::begin_operation(&self.connection, name)
+    }
+
+    fn undo(&mut self) -> Result<Option<String>> {
+        history::undo(&mut self.connection)
+    }
+
+    fn redo(&mut self) -> Result<Option<String>> {
+        history::redo(&mut self.connection)
+    }
+
+    fn search(&self, input: &str, limit: usize) -> Result<Vec<SearchHit>> {
+        search::search(&self.connection, input, limit)
+    }
+
+    fn changed_elsewhere(&mut self) -> Result<bool> {
+        let data_version = data_version(&self.connection)?;
+        let changed = data_version != self.data_version;
+        self.data_version = data_version;
+
+        Ok(changed)
+    }
+}
@@ -0,0 +1,106 @@
+use ratatui::style::Color;
+
+use chrono::NaiveDateTime;
+
+use super::{Badge, BadgeTransition, Note, Result, SearchHit, Task, TaskList, TimeEntry, TrashedTask, Dependency};
+
+// Everything `TaskStore` reads from or writes to. The store keeps its own copy of the
+// current list and does the ordering and tree bookkeeping, a storage only has to keep rows.
+// Each call is expected to either apply completely or not at all, calls between
+// `begin_transaction` and `commit_transaction` as a whole.
+pub trait Storage {
+    // where the data lives, shown to the user
+    fn location(&self) -> String;
+
+    // lists ordered by their sort order, there's always at least one
+    fn load_lists(&self) -> Result<Vec<TaskList>>;
+    fn load_badges(&self) -> Result<Vec<Badge>>;
+    fn load_notes(&self) -> Result<Vec<Note>>;
+    // tasks of the list ordered by their sort order
+    fn load_tasks(&self, list_id: i64) -> Result<Vec<Task>>;
+
+    // appended after the other lists
+    fn insert_list(&mut self, name: &str) -> Result<i64>;
+    fn rename_list(&mut self, list_id: i64, name: &str) -> Result<()>;
+    // removes the list together with its tasks, their notes, time entries and dependencies
+    fn delete_list(&mut self, list_id: i64) -> Result<()>;
+
+    // appended after the other badges
+    fn insert_badge(&mut self, name: &str, color: Color) -> Result<Badge>;
+    fn update_badge(&mut self, badge: &Badge) -> Result<()>;
+    fn update_badges(&mut self, badges: &[&Badge]) -> Result<()> {
+        badges.iter().try_for_each(|badge| self.update_badge(badge))
+    }
+    // tasks of every list carrying the badge, trashed ones included, get completed at that time
+    // or lose their completion time. Tasks that already have the time asked for are left alone,
+    // the others get their revision bumped.
+    fn update_badge_completed_at(&mut self, badge_id: i64, completed_at: Option<NaiveDateTime>) -> Result<()>;
+    // tasks of every list carrying the badge get `replacement_id` instead, the transitions
+    // from and to the badge go away with it
+    fn delete_badge(&mut self, badge_id: i64, replacement_id: Option<i64>) -> Result<()>;
+
+    fn load_badge_transitions(&self) -> Result<Vec<BadgeTransition>>;
+    fn insert_badge_transition(&mut self, from_id: i64, to_id: i64) -> Result<i64>;
+    fn delete_badge_transition(&mut self, transition_id: i64) -> Result<()>;
+
+    // updates of notes and tasks bump their revision, a storage shared with other processes
+    // fails with `Conflict` instead of writing when the stored revision is a different one
+    fn insert_note(&mut self, note: &Note) -> Result<i64>;
+    fn update_note(&mut self, note: &Note) -> Result<()>;
+
+    fn insert_task(&mut self, list_id: i64, task: &Task) -> Result<i64>;
+    // writes every field of the task, the list it belongs to stays the same
+    fn update_task(&mut self, task: &Task) -> Result<()>;
+    fn update_tasks(&mut self, tasks: &[&Task]) -> Result<()> {
+        tasks.iter().try_for_each(|task| self.update_task(task))
+    }
+    // moves the task to the trash, its subtasks move up to its parent
+    fn trash_task(&mut self, task_id: i64, deleted_at: NaiveDateTime) -> Result<()>;
+    // tasks in the trash of every list, most recently deleted first
+    fn load_trash(&self) -> Result<Vec<TrashedTask>>;
+    // takes the task out of the trash with the list, parent and sort order it had
+    fn restore_task(&mut self, task_id: i64) -> Result<()>;
+    // deletes tasks in the trash for good with their time entries and dependencies, together
+    // with every note no task refers to anymore
+    fn purge_tasks(&mut self, task_ids: &[i64]) -> Result<()>;
+    // appends the tasks in the given order to the other list, the first one is the root of
+    // the moved subtree and loses its parent
+    fn move_tasks_to_list(&mut self, task_ids: &[i64], list_id: i64) -> Result<()>;
+
+    // entries of every task including the trashed ones, oldest first
+    fn load_time_entries(&self) -> Result<Vec<TimeEntry>>;
+    fn insert_time_entry(&mut self, entry: &TimeEntry) -> Result<i64>;
+    fn update_time_entry(&mut self, entry: &TimeEntry) -> Result<()>;
+
+    // dependencies between tasks of every list
+    fn load_dependencies(&self) -> Result<Vec<Dependency>>;
+    fn insert_dependency(&mut self, task_id: i64, blocker_id: i64) -> Result<i64>;
+    fn delete_dependency(&mut self, dependency_id: i64) -> Result<()>;
+
+    // a rollback takes back every call made since the transaction began
+    fn begin_transaction(&mut self) -> Result<()>;
+    fn commit_transaction(&mut self) -> Result<()>;
+    fn rollback_transaction(&mut self) -> Result<()>;
+
+    // undo history and search are optional, a storage without them gets by with these
+    fn begin_operation(&mut self, _name: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn undo(&mut self) -> Result<Option<String>> {
+        Err("this storage has no undo history".into())
+    }
+
+    fn redo(&mut self) -> Result<Option<String>> {
+        Err("this storage has no undo history".into())
+    }
+
+    fn search(&self, _input: &str, _limit: usize) -> Result<Vec<SearchHit>> {
+        Err("this storage can't be searched".into())
+    }
+
+    // whether someone else wrote to the data since the last call, nobody else can by default
+    fn changed_elsewhere(&mut self) -> Result<bool> {
+        Ok(false)
+    }
+}
@@ -0,0 +1,228 @@
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone, Weekday};
+
+use crate::{dates::{self, parse_basic_timestamp, Recurrence}, format::ical::{escape_text, unescape_text, Component, Property}, store::{Badge, ImportedTask, Task, TaskStore}};
+
+// badges calendar clients have a status for, a task in progress and one that was dropped
+const IN_PROCESS_BADGE: &str = "In Progress";
+const CANCELLED_BADGE: &str = "Cancelled";
+
+const WEEKDAYS: [(Weekday, &str); 7] = [
+    (Weekday::Mon, "MO"),
+    (Weekday::Tue, "TU"),
+    (Weekday::Wed, "WE"),
+    (Weekday::Thu, "TH"),
+    (Weekday::Fri, "FR"),
+    (Weekday::Sat, "SA"),
+    (Weekday::Sun, "SU")
+];
+
+fn task_uid(task_id: i64) -> String {
+    format!("letter-task-{task_id}")
+}
+
+// letter keeps local time, timestamps in the file are in UTC
+fn utc_timestamp(timestamp: NaiveDateTime) -> String {
+    let utc = Local.from_local_datetime(&timestamp).earliest().map_or(timestamp, |local| local.naive_utc());
+    utc.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn ical_date(date: NaiveDate) -> String {
+    date.format("%Y%m%d").to_string()
+}
+
+// `after:3d` has no rule, it's kept in X-LETTER-REPEAT instead
+fn rrule(recurrence: &Recurrence) -> Option<String> {
+    match recurrence {
+        Recurrence::Daily => Some("FREQ=DAILY".to_string()),
+        Recurrence::Weekly(weekdays) if weekdays.is_empty() => Some("FREQ=WEEKLY".to_string()),
+        Recurrence::Weekly(weekdays) => {
+            let days: Vec<&str> = WEEKDAYS.iter().filter(|(weekday, _)| weekdays.contains(weekday)).map(|(_, day)| *day).collect();
+            Some(format!("FREQ=WEEKLY;BYDAY={}", days.join(",")))
+        },
+        Recurrence::Monthly(None) => Some("FREQ=MONTHLY".to_string()),
+        Recurrence::Monthly(Some(day @ 1..=28)) => Some(format!("FREQ=MONTHLY;BYMONTHDAY={day}")),
+        // months without the day would be skipped, the last of the days up to it is taken instead
+        Recurrence::Monthly(Some(day)) => {
+            let days: Vec<String> = (28..=*day).map(|day| day.to_string()).collect();
+            Some(format!("FREQ=MONTHLY;BYMONTHDAY={};BYSETPOS=-1", days.join(",")))
+        },
+        Recurrence::AfterCompletion(_) => None
+    }
+}
+
+// None for rules letter can't repeat by, like every other week or the first monday of a month
+fn parse_rrule(value: &str) -> Option<Recurrence> {
+    let (mut frequency, mut days, mut month_days, mut last_of) = (None, None, None, false);
+    for part in value.split(';') {
+        match part.split_once('=')? {
+            ("FREQ", value) => frequency = Some(value),
+            ("BYDAY", value) => days = Some(value),
+            ("BYMONTHDAY", value) => month_days = Some(value.split(',').map(|day| day.parse::<u32>().ok()).collect::<Option<Vec<_>>>()?),
+            ("BYSETPOS", "-1") => last_of = true,
+            ("INTERVAL", "1") | ("WKST", _) => {},
+            _ => return None
+        }
+    }
+
+    match (frequency?, days, month_days.as_deref(), last_of) {
+        ("DAILY", None, None, false) => Some(Recurrence::Daily),
+        ("WEEKLY", None, None, false) => Some(Recurrence::Weekly(vec![])),
+        ("WEEKLY", Some(days), None, false) => {
+            let weekdays = WEEKDAYS.iter().filter(|(_, day)| days.split(',').any(|value| value == *day)).map(|(weekday, _)| *weekday).collect::<Vec<_>>();
+            match weekdays.len() == days.split(',').count() {
+                true => Some(Recurrence::Weekly(weekdays)),
+                false => None
+            }
+        },
+        ("MONTHLY", None, None, false) => Some(Recurrence::Monthly(None)),
+        ("MONTHLY", None, Some([day @ 1..=28]), false) => Some(Recurrence::Monthly(Some(*day))),
+        // the way a day past the 28th is exported
+        ("MONTHLY", None, Some([28, later @ ..]), true) if later.iter().copied().eq(29..=28 + later.len() as u32) && later.len() <= 3 => {
+            Some(Recurrence::Monthly(Some(28 + later.len() as u32)))
+        },
+        _ => None
+    }
+}
+
+// done badges are completed, the rest only differs in the badge name
+fn status(badge: Option<&Badge>) -> &'static str {
+    match badge {
+        Some(badge) if badge.name.eq_ignore_ascii_case(CANCELLED_BADGE) => "CANCELLED",
+        Some(badge) if badge.is_done() => "COMPLETED",
+        Some(badge) if badge.name.eq_ignore_ascii_case(IN_PROCESS_BADGE) => "IN-PROCESS",
+        _ => "NEEDS-ACTION"
+    }
+}
+
+fn todo(store: &TaskStore, task: &Task, done_id: Option<i64>, stamp: &str) -> Component {
+    let mut todo = Component::new("VTODO");
+    let badge = store.get_badge(task);
+    let properties = &mut todo.properties;
+
+    properties.push(Property::new("UID", &task_uid(task.id.unwrap_or_default())));
+    properties.push(Property::new("DTSTAMP", stamp));
+    properties.extend(task.created_at.map(|created_at| Property::new("CREATED", &utc_timestamp(created_at))));
+    properties.extend(task.updated_at.map(|updated_at| Property::new("LAST-MODIFIED", &utc_timestamp(updated_at))));
+    properties.push(Property::new("SUMMARY", &escape_text(&task.text)));
+    properties.extend(task.note_id
+        .and_then(|note_id| store.get_note_by_id(note_id))
+        .filter(|note| !note.text.is_empty())
+        .map(|note| Property::new("DESCRIPTION", &escape_text(&note.text))));
+    properties.push(Property::new("STATUS", status(badge)));
+    properties.extend(task.completed_at.map(|completed_at| Property::new("COMPLETED", &utc_timestamp(completed_at))));
+    properties.extend(task.start_date.map(|date| Property::new("DTSTART", &ical_date(date)).with_param("VALUE", "DATE")));
+    properties.extend(task.due_date.map(|date| Property::new("DUE", &ical_date(date)).with_param("VALUE", "DATE")));
+    if let Some(recurrence) = &task.recurrence {
+        match rrule(recurrence) {
+            Some(rule) => properties.push(Property::new("RRULE", &rule)),
+            None => properties.push(Property::new("X-LETTER-REPEAT", &recurrence.to_string()))
+        }
+    }
+    properties.extend(task.parent_id.map(|parent_id| Property::new("RELATED-TO", &task_uid(parent_id)).with_param("RELTYPE", "PARENT")));
+    properties.extend(badge
+        .filter(|badge| Some(badge.id) != done_id)
+        .map(|badge| Property::new("X-LETTER-BADGE", &escape_text(&badge.name))));
+
+    todo
+}
+
+// A calendar named after the list with a VTODO for every task, subtasks refer to their parent.
+// The badge is kept in X-LETTER-BADGE unless it's the first done one, which STATUS implies.
+pub fn export(store: &TaskStore) -> String {
+    let done_id = store.sorted_badges().iter().find(|badge| badge.is_done()).map(|badge| badge.id);
+    let stamp = utc_timestamp(dates::now());
+
+    let mut calendar = Component::new("VCALENDAR");
+    calendar.properties.push(Property::new("VERSION", "2.0"));
+    calendar.properties.push(Property::new("PRODID", "-//letter//letter//EN"));
+    calendar.properties.extend(store.current_list().map(|list| Property::new("X-WR-CALNAME", &escape_text(&list.name))));
+    calendar.components.extend(store.tasks.iter().map(|task| todo(store, task, done_id, &stamp)));
+
+    calendar.render()
+}
+
+// Every VTODO of the file, other components like events are left out. A task is a subtask if
+// its parent came right before it, the badge comes from X-LETTER-BADGE or else from STATUS.
+pub fn import(content: &str) -> Vec<ImportedTask> {
+    let calendars = Component::parse(content);
+    let mut ancestors: Vec<String> = vec![];
+
+    calendars.iter()
+        .flat_map(|calendar| calendar.find_all("VTODO"))
+        .map(|todo| {
+            let text = |name: &str| todo.property(name).map(|property| unescape_text(&property.value));
+            let timestamp = |name: &str| todo.property(name).and_then(|property| parse_basic_timestamp(&property.value));
+
+            let task = Task {
+                text: text("SUMMARY").unwrap_or_default(),
+                due_date: timestamp("DUE").map(|due| due.date()),
+                start_date: timestamp("DTSTART").map(|start| start.date()),
+                created_at: timestamp("CREATED"),
+                completed_at: timestamp("COMPLETED"),
+                recurrence: todo.property("RRULE")
+                    .and_then(|property| parse_rrule(&property.value))
+                    .or_else(|| todo.property("X-LETTER-REPEAT")?.value.parse().ok()),
+                ..Default::default()
+            };
+
+            let status = todo.property("STATUS").map(|property| property.value.to_uppercase());
+            let badge = text("X-LETTER-BADGE").or_else(|| match status.as_deref() {
+                Some("IN-PROCESS") => Some(IN_PROCESS_BADGE.to_string()),
+                Some("CANCELLED") => Some(CANCELLED_BADGE.to_string()),
+                _ => None
+            });
+            let done = matches!(status.as_deref(), Some("COMPLETED" | "CANCELLED")) || task.completed_at.is_some();
+
+            let parent = todo.properties.iter()
+                .find(|property| property.name == "RELATED-TO" && property.param("RELTYPE").is_none_or(|reltype| reltype.eq_ignore_ascii_case("PARENT")))
+                .map(|property| property.value.clone());
+            let depth = parent.and_then(|parent| ancestors.iter().position(|uid| *uid == parent)).map_or(0, |position| position + 1);
+            ancestors.truncate(depth);
+            ancestors.push(todo.property("UID").map(|property| property.value.clone()).unwrap_or_default());
+
+            ImportedTask {
+                task,
+                depth,
+                badge,
+                done,
+                note: text("DESCRIPTION").filter(|note| !note.is_empty())
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_rules() {
+        for rule in ["daily", "weekly", "weekly:mon,fri", "monthly", "monthly:15", "monthly:29", "monthly:31"] {
+            let recurrence: Recurrence = rule.parse().unwrap();
+            assert_eq!(rrule(&recurrence).and_then(|rule| parse_rrule(&rule)), Some(recurrence), "{rule}");
+        }
+
+        assert_eq!(rrule(&Recurrence::AfterCompletion(3)), None);
+    }
+
+    #[test]
+    fn leaves_out_unknown_rules() {
+        assert_eq!(parse_rrule("FREQ=WEEKLY;INTERVAL=2"), None);
+        assert_eq!(parse_rrule("FREQ=MONTHLY;BYDAY=1MO"), None);
+        assert_eq!(parse_rrule("FREQ=MONTHLY;BYMONTHDAY=27,28;BYSETPOS=-1"), None);
+        assert_eq!(parse_rrule("FREQ=WEEKLY;BYDAY=MO;WKST=SU"), Some(Recurrence::Weekly(vec![Weekday::Mon])));
+    }
+
+    #[test]
+    fn imports_todos() {
+        let content = "BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\nUID:a\r\nSUMMARY:plan\\, then ship\r\nSTATUS:IN-PROCESS\r\nDUE;VALUE=DATE:20240503\r\nEND:VTODO\r\nBEGIN:VTODO\r\nUID:b\r\nRELATED-TO:a\r\nSUMMARY:ship\r\nSTATUS:COMPLETED\r\nDESCRIPTION:notes\r\nEND:VTODO\r\nBEGIN:VEVENT\r\nSUMMARY:meeting\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+        let tasks = import(content);
+
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].task.text, "plan, then ship");
+        assert_eq!(tasks[0].task.due_date, NaiveDate::from_ymd_opt(2024, 5, 3));
+        assert_eq!(tasks[0].badge.as_deref(), Some(IN_PROCESS_BADGE));
+        assert_eq!((tasks[1].depth, tasks[1].done), (1, true));
+        assert_eq!(tasks[1].note.as_deref(), Some("notes"));
+    }
+}
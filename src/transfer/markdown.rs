@@ -0,0 +1,49 @@
+use crate::{format::markdown::{Checklist, ChecklistItem}, store::{ImportedTask, Task, TaskStore}};
+
+// Done tasks are checked. The badge goes in front of the text as `[name]`, except for the
+// first done badge since a checked item without a badge stands for that one already.
+pub fn export(store: &TaskStore) -> String {
+    let done_id = store.sorted_badges().iter().find(|badge| badge.is_done()).map(|badge| badge.id);
+
+    let items = store.tasks.iter()
+        .zip(store.task_depths())
+        .map(|(task, depth)| ChecklistItem {
+            depth,
+            checked: store.is_done(task),
+            badge: store.get_badge(task)
+                .filter(|badge| Some(badge.id) != done_id)
+                .map(|badge| badge.name.clone()),
+            text: task.text.clone(),
+            note: task.note_id
+                .and_then(|note_id| store.get_note_by_id(note_id))
+                .map(|note| note.text.clone())
+                .filter(|text| !text.is_empty()),
+            due_date: task.due_date,
+            start_date: task.start_date,
+            recurrence: task.recurrence.clone(),
+            ..Default::default()
+        })
+        .collect();
+
+    Checklist::new(items).render()
+}
+
+// every item of the checklist, whatever else the text holds is left out
+pub fn import(content: &str) -> Vec<ImportedTask> {
+    Checklist::parse(content).items
+        .into_iter()
+        .map(|item| ImportedTask {
+            task: Task {
+                text: item.text,
+                due_date: item.due_date,
+                start_date: item.start_date,
+                recurrence: item.recurrence,
+                ..Default::default()
+            },
+            depth: item.depth,
+            badge: item.badge,
+            done: item.checked,
+            note: item.note
+        })
+        .collect()
+}
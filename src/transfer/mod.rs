@@ -0,0 +1,6 @@
+// Moves tasks between the current list of a `TaskStore` and other formats, the formats
+// themselves are read and written by `crate::format`.
+pub mod markdown;
+pub mod ical;
+pub mod todotxt;
+pub mod taskwarrior;
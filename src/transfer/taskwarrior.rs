@@ -0,0 +1,209 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::Weekday;
+use serde_json::{Map, Value};
+
+use crate::{cli::TaskOrder, dates::{self, parse_basic_timestamp, Recurrence}, store::{ImportedTask, Task, TaskStore}, Result};
+
+// fields that are imported or only matter to Taskwarrior itself, like the urgency it computed
+const KNOWN_FIELDS: &[&str] = &[
+    "id", "uuid", "description", "status", "tags", "annotations", "entry", "modified", "end", "start",
+    "wait", "due", "scheduled", "recur", "urgency", "mask", "imask"
+];
+
+// badges for the states Taskwarrior has but letter has no flag for
+const WAITING_BADGE: &str = "Waiting";
+const ACTIVE_BADGE: &str = "In Progress";
+
+// `task export` writes an array, older versions write one object per line
+fn parse_entries(content: &str) -> Result<Vec<Map<String, Value>>> {
+    let values: Vec<Value> = match serde_json::from_str(content) {
+        Ok(Value::Array(values)) => values,
+        Ok(value) => vec![value],
+        Err(_) => content.lines()
+            .map(|line| line.trim().trim_end_matches(','))
+            .filter(|line| !matches!(*line, "" | "[" | "]"))
+            .map(serde_json::from_str)
+            .collect::<serde_json::Result<_>>()?
+    };
+
+    values.into_iter()
+        .map(|value| match value {
+            Value::Object(entry) if entry.get("description").is_some_and(Value::is_string) => Ok(entry),
+            _ => Err("that's not a Taskwarrior export, it holds something other than tasks".into())
+        })
+        .collect()
+}
+
+// None for periods letter can't repeat by, like every other week
+fn parse_recur(recur: &str) -> Option<Recurrence> {
+    match recur {
+        "daily" | "day" | "1d" | "P1D" => Some(Recurrence::Daily),
+        "weekly" | "1w" | "1wk" | "7d" | "P1W" | "P7D" => Some(Recurrence::Weekly(vec![])),
+        "weekdays" => Some(Recurrence::Weekly(vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri])),
+        "monthly" | "1mo" | "P1M" => Some(Recurrence::Monthly(None)),
+        _ => None
+    }
+}
+
+fn urgency(entry: &Map<String, Value>) -> f64 {
+    entry.get("urgency").and_then(Value::as_f64).unwrap_or(0.0)
+}
+
+fn text<'a>(entry: &'a Map<String, Value>, name: &str) -> Option<&'a str> {
+    entry.get(name).and_then(Value::as_str)
+}
+
+// Every task of a `task export` JSON, deleted tasks and the templates recurring tasks are made
+// from are left out. Tags naming a badge pick it, the other ones stay in the text as `+tag`,
+// and annotations become the note. Returns the tasks and what couldn't be imported.
+pub fn import(content: &str, store: &TaskStore, order: TaskOrder) -> Result<(Vec<ImportedTask>, Vec<String>)> {
+    let mut entries = parse_entries(content)?;
+    let mut report = vec![];
+
+    for (status, left_out) in [("deleted", "deleted tasks"), ("recurring", "templates of recurring tasks")] {
+        let count = entries.iter().filter(|entry| text(entry, "status") == Some(status)).count();
+        if count > 0 {
+            entries.retain(|entry| text(entry, "status") != Some(status));
+            report.push(format!("left out {count} {left_out}"));
+        }
+    }
+
+    let entered = |entry: &Map<String, Value>| text(entry, "entry").and_then(parse_basic_timestamp);
+    match order {
+        TaskOrder::Urgency => entries.sort_by(|a, b| urgency(b).total_cmp(&urgency(a)).then_with(|| entered(a).cmp(&entered(b)))),
+        TaskOrder::Entry => entries.sort_by_key(entered)
+    }
+
+    // every child of a recurring task carries its `recur`, only the newest pending one of each
+    // parent keeps repeating so there's a single chain of occurrences
+    let mut newest_children: HashMap<&str, usize> = HashMap::new();
+    for (idx, entry) in entries.iter().enumerate().filter(|(_, entry)| text(entry, "status") != Some("completed")) {
+        let Some(parent) = text(entry, "parent") else {
+            continue
+        };
+        let due = |entry: &Map<String, Value>| (text(entry, "due").and_then(parse_basic_timestamp), entered(entry));
+        let newest = newest_children.entry(parent).or_insert(idx);
+        if due(entry) > due(&entries[*newest]) {
+            *newest = idx;
+        }
+    }
+    let repeats = |idx: usize, entry: &Map<String, Value>| match text(entry, "parent") {
+        Some(parent) => newest_children.get(parent) == Some(&idx),
+        None => text(entry, "status") != Some("completed")
+    };
+
+    let badges = store.sorted_badges();
+    let now = dates::now();
+    let mut unmapped: BTreeMap<String, usize> = BTreeMap::new();
+
+    let tasks = entries.iter()
+        .enumerate()
+        .map(|(idx, entry)| {
+            for field in entry.keys().filter(|field| !KNOWN_FIELDS.contains(&field.as_str())) {
+                *unmapped.entry(field.clone()).or_default() += 1;
+            }
+
+            let timestamp = |name: &str| text(entry, name).and_then(parse_basic_timestamp);
+            let status = text(entry, "status");
+            let done = status == Some("completed");
+
+            let recurrence = text(entry, "recur").filter(|_| repeats(idx, entry)).and_then(|recur| {
+                let recurrence = parse_recur(recur);
+                if recurrence.is_none() {
+                    *unmapped.entry("recur".to_string()).or_default() += 1;
+                }
+                recurrence
+            });
+
+            // a badge only counts if it's done just when the task is
+            let tags: Vec<&str> = entry.get("tags").and_then(Value::as_array).into_iter().flatten().filter_map(Value::as_str).collect();
+            let tag_badge = tags.iter().copied().find(|tag| badges.iter()
+                .any(|badge| badge.name.eq_ignore_ascii_case(&tag.replace('_', " ")) && badge.is_done() == done));
+            let waiting = status == Some("waiting") || timestamp("wait").is_some_and(|wait| wait > now);
+            let badge = match tag_badge {
+                Some(tag) => Some(tag.replace('_', " ")),
+                None if !done && waiting => Some(WAITING_BADGE.to_string()),
+                None if !done && entry.contains_key("start") => Some(ACTIVE_BADGE.to_string()),
+                None => None
+            };
+
+            let mut words = vec![text(entry, "description").unwrap_or_default().to_string()];
+            words.extend(tags.iter().filter(|tag| Some(**tag) != tag_badge).map(|tag| format!("+{tag}")));
+
+            // an annotation per line, starting with the day it was made
+            let annotations: Vec<String> = entry.get("annotations").and_then(Value::as_array).into_iter().flatten()
+                .filter_map(|annotation| {
+                    let description = annotation.get("description")?.as_str()?;
+                    match annotation.get("entry").and_then(Value::as_str).and_then(parse_basic_timestamp) {
+                        Some(made_at) => Some(format!("{} {description}", made_at.date())),
+                        None => Some(description.to_string())
+                    }
+                })
+                .collect();
+
+            ImportedTask {
+                task: Task {
+                    text: words.join(" "),
+                    due_date: timestamp("due").map(|due| due.date()),
+                    start_date: timestamp("scheduled").map(|scheduled| scheduled.date()),
+                    created_at: timestamp("entry"),
+                    completed_at: timestamp("end").filter(|_| done),
+                    recurrence,
+                    ..Default::default()
+                },
+                depth: 0,
+                badge,
+                done,
+                note: Some(annotations.join("\n")).filter(|note| !note.is_empty())
+            }
+        })
+        .collect();
+
+    report.extend(unmapped.iter().map(|(field, count)| format!("couldn't import {field} of {count} tasks")));
+    Ok((tasks, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::store::MemoryStorage;
+
+    use super::*;
+
+    fn store() -> TaskStore {
+        let mut store = TaskStore::new(Box::new(MemoryStorage::new()));
+        store.fetch_data().unwrap();
+        store
+    }
+
+    #[test]
+    fn only_the_newest_pending_child_repeats() {
+        let content = r#"[
+            {"uuid": "p", "description": "standup", "status": "recurring", "recur": "daily", "due": "20240501T090000Z"},
+            {"uuid": "a", "description": "standup", "status": "completed", "recur": "daily", "parent": "p", "due": "20240501T090000Z"},
+            {"uuid": "c", "description": "standup", "status": "pending", "recur": "daily", "parent": "p", "due": "20240503T090000Z"},
+            {"uuid": "b", "description": "standup", "status": "pending", "recur": "daily", "parent": "p", "due": "20240502T090000Z"}
+        ]"#;
+        let (tasks, report) = import(content, &store(), TaskOrder::Entry).unwrap();
+
+        let repeating: Vec<_> = tasks.iter().filter(|task| task.task.recurrence.is_some()).collect();
+        assert_eq!(repeating.len(), 1);
+        assert_eq!(repeating[0].task.due_date.map(|due| due.to_string()).as_deref(), Some("2024-05-03"));
+        assert!(report.contains(&"couldn't import parent of 3 tasks".to_string()));
+    }
+
+    #[test]
+    fn reads_lines_of_objects() {
+        let content = "{\"description\": \"a\", \"tags\": [\"next\", \"done\"], \"status\": \"completed\"}\n{\"description\": \"b\", \"start\": \"20240501\"}\n";
+        let (tasks, _) = import(content, &store(), TaskOrder::Entry).unwrap();
+
+        assert_eq!(tasks[0].task.text, "a +next");
+        assert_eq!((tasks[0].badge.as_deref(), tasks[0].done), (Some("done"), true));
+        assert_eq!(tasks[1].badge.as_deref(), Some(ACTIVE_BADGE));
+    }
+
+    #[test]
+    fn refuses_other_json() {
+        assert!(import(r#"{"version": 1}"#, &store(), TaskOrder::Entry).is_err());
+    }
+}
@@ -0,0 +1,107 @@
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::{dates::Recurrence, format::todotxt::{parse_extra, TodoItem}, store::{ImportedTask, Task, TaskStore}};
+
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()
+}
+
+fn midnight(date: NaiveDate) -> Option<NaiveDateTime> {
+    date.and_hms_opt(0, 0, 0)
+}
+
+// a priority stays in front of the text, `(A) call mom`
+fn split_priority(text: &str) -> (Option<char>, &str) {
+    match text.as_bytes() {
+        [b'(', priority @ b'A'..=b'Z', b')', b' ', ..] => (Some(*priority as char), &text[4..]),
+        _ => (None, text)
+    }
+}
+
+// Every task of the list becomes a line, subtasks included but without their nesting and
+// notes are left out. Dates, the recurrence and the badge are written as `due:`, `t:`, `repeat:`
+// and `badge:` extras, the first done badge is implied by `x` already.
+pub fn export(store: &TaskStore) -> String {
+    let done_id = store.sorted_badges().iter().find(|badge| badge.is_done()).map(|badge| badge.id);
+
+    store.tasks.iter()
+        .map(|task| {
+            let (priority, text) = split_priority(&task.text);
+
+            let mut tokens = vec![text.to_string()];
+            tokens.extend(task.due_date.map(|date| format!("due:{date}")));
+            tokens.extend(task.start_date.map(|date| format!("t:{date}")));
+            tokens.extend(task.recurrence.as_ref().map(|recurrence| format!("repeat:{recurrence}")));
+            tokens.extend(store.get_badge(task)
+                .filter(|badge| Some(badge.id) != done_id)
+                .map(|badge| format!("badge:{}", badge.name.replace(' ', "_"))));
+
+            let item = TodoItem {
+                done: store.is_done(task),
+                priority,
+                completed_on: task.completed_at.map(|completed_at| completed_at.date()),
+                created_on: task.created_at.map(|created_at| created_at.date()),
+                description: tokens.into_iter().filter(|token| !token.is_empty()).collect::<Vec<_>>().join(" ")
+            };
+            item.render() + "\n"
+        })
+        .collect()
+}
+
+// extras letter doesn't know about stay in the text together with projects and contexts
+pub fn import(content: &str) -> Vec<ImportedTask> {
+    content.lines()
+        .filter_map(TodoItem::parse)
+        .map(|item| {
+            let mut task = Task {
+                created_at: item.created_on.and_then(midnight),
+                completed_at: item.completed_on.and_then(midnight),
+                ..Default::default()
+            };
+            let mut badge = None;
+
+            let mut words: Vec<String> = item.priority.map(|priority| format!("({priority})")).into_iter().collect();
+            for token in item.description.split(' ') {
+                let known = match parse_extra(token) {
+                    Some(("due", value)) => parse_date(value).map(|date| task.due_date = Some(date)).is_some(),
+                    Some(("t", value)) => parse_date(value).map(|date| task.start_date = Some(date)).is_some(),
+                    Some(("repeat", value)) => value.parse::<Recurrence>().ok().map(|rule| task.recurrence = Some(rule)).is_some(),
+                    Some(("badge", value)) => {
+                        badge = Some(value.replace('_', " "));
+                        true
+                    },
+                    _ => false
+                };
+
+                if !known && !token.is_empty() {
+                    words.push(token.to_string());
+                }
+            }
+            task.text = words.join(" ");
+
+            ImportedTask { task, depth: 0, badge, done: item.done, note: None }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imports_known_extras() {
+        let tasks = import("(A) call mom +family due:2024-05-03 t:2024-05-01 repeat:weekly badge:In_Progress id:7\n\nx 2024-05-02 done\n");
+        assert_eq!(tasks.len(), 2);
+
+        let task = &tasks[0].task;
+        assert_eq!(task.text, "(A) call mom +family id:7");
+        assert_eq!(task.due_date, NaiveDate::from_ymd_opt(2024, 5, 3));
+        assert_eq!(task.start_date, NaiveDate::from_ymd_opt(2024, 5, 1));
+        assert_eq!(task.recurrence, Some(Recurrence::Weekly(vec![])));
+        assert_eq!(tasks[0].badge.as_deref(), Some("In Progress"));
+        assert!(!tasks[0].done);
+
+        assert!(tasks[1].done);
+        assert_eq!(tasks[1].task.completed_at, NaiveDate::from_ymd_opt(2024, 5, 2).and_then(midnight));
+    }
+}
@@ -0,0 +1,428 @@
+use crossterm::event::KeyCode;
+use log::error;
+use ratatui::{prelude::{Rect, Layout, Direction, Constraint}, widgets::{Block, Borders, Clear, List, ListItem, Paragraph}, style::{Style, Color, Modifier}, text::{Line, Span}};
+
+use crate::{store::TaskStore, ui::{textarea::TextArea, popup_rect}, Frame, LetterCommand, LetterEvent, LetterState, Window, WindowCommand, _WindowCommand};
+
+const NEW_BADGE_COLOR: [u8; 3] = [0xA0, 0xC4, 0xFF];
+const CHANNEL_NAMES: [&str; 3] = ["red", "green", "blue"];
+
+enum ColorTarget {
+    Create(String),
+    Recolor(i64)
+}
+
+enum Mode {
+    Browse,
+    Create(TextArea<LetterState, LetterCommand>),
+    Rename(i64, TextArea<LetterState, LetterCommand>),
+    PickColor {
+        target: ColorTarget,
+        rgb: [u8; 3],
+        channel: usize
+    },
+    // picks the badges that may follow the badge, any may if none is picked
+    Transitions {
+        badge_id: i64,
+        cursor: usize
+    },
+    // picks what the tasks of a deleted badge get instead, None clears their badge
+    Delete {
+        badge_id: i64,
+        replacements: Vec<Option<i64>>,
+        cursor: usize
+    }
+}
+
+pub struct BadgeEditorWindow {
+    cursor: usize,
+    mode: Mode,
+    pending_delete: bool,
+    error: Option<String>
+}
+
+impl BadgeEditorWindow {
+    pub fn new() -> Self {
+        Self {
+            cursor: 0,
+            mode: Mode::Browse,
+            pending_delete: false,
+            error: None
+        }
+    }
+
+    fn name_input(name: &str) -> TextArea<LetterState, LetterCommand> {
+        let mut text_area = TextArea::new(vec![String::from(name)]);
+        text_area.disallow_line_breaks();
+        text_area.move_cursor_to_line_end();
+        text_area
+    }
+
+    fn selected_badge_id(&self, store: &TaskStore) -> Option<i64> {
+        store.sorted_badges().get(self.cursor).map(|badge| badge.id)
+    }
+
+    fn handle_browse(&mut self, store: &mut TaskStore, key_code: KeyCode) -> WindowCommand {
+        let pending_delete = std::mem::take(&mut self.pending_delete);
+        let badge_count = store.badges.len();
+
+        match key_code {
+            KeyCode::Esc | KeyCode::Char('q') => return Some(_WindowCommand::Quit),
+            KeyCode::Char('n') => self.mode = Mode::Create(Self::name_input("")),
+            KeyCode::Char('j') | KeyCode::Down if badge_count > 0 => self.cursor = (self.cursor + 1) % badge_count,
+            KeyCode::Char('k') | KeyCode::Up if badge_count > 0 => self.cursor = (self.cursor + badge_count - 1) % badge_count,
+            _ => {}
+        }
+
+        let badge_id = self.selected_badge_id(store)?;
+        match key_code {
+            KeyCode::Char('r') => {
+                let name = store.badges[&badge_id].name.clone();
+                self.mode = Mode::Rename(badge_id, Self::name_input(&name));
+            },
+            KeyCode::Char('c') => {
+                let rgb = match store.badges[&badge_id].color {
+                    Color::Rgb(r, g, b) => [r, g, b],
+                    _ => NEW_BADGE_COLOR
+                };
+                self.mode = Mode::PickColor { target: ColorTarget::Recolor(badge_id), rgb, channel: 0 };
+            },
+            KeyCode::Char('x') => {
+                let done = store.badges[&badge_id].done;
+                if let Err(err) = store.set_badge_done(badge_id, !done) {
+                    self.error = Some(err.to_string());
+                }
+            },
+            KeyCode::Char('a') => self.mode = Mode::Transitions { badge_id, cursor: 0 },
+            KeyCode::Char('J') if self.cursor + 1 < badge_count => {
+                match store.move_badge(badge_id, self.cursor + 1) {
+                    Ok(_) => self.cursor += 1,
+                    Err(_) => error!("couldn't move badge {badge_id}")
+                }
+            },
+            KeyCode::Char('K') if self.cursor > 0 => {
+                match store.move_badge(badge_id, self.cursor - 1) {
+                    Ok(_) => self.cursor -= 1,
+                    Err(_) => error!("couldn't move badge {badge_id}")
+                }
+            },
+            KeyCode::Char('d') if pending_delete => {
+                let mut replacements: Vec<Option<i64>> = vec![None];
+                replacements.extend(store.sorted_badges().iter()
+                    .filter(|badge| badge.id != badge_id)
+                    .map(|badge| Some(badge.id)));
+
+                self.mode = Mode::Delete { badge_id, replacements, cursor: 0 };
+            },
+            KeyCode::Char('d') => self.pending_delete = true,
+            _ => {}
+        }
+
+        None
+    }
+
+    fn handle_name_input(&mut self, store: &mut TaskStore, key_code: KeyCode) {
+        let (Mode::Create(text_area) | Mode::Rename(_, text_area)) = &mut self.mode else {
+            return
+        };
+
+        match key_code {
+            KeyCode::Esc => self.mode = Mode::Browse,
+            KeyCode::Enter => {
+                let name = text_area.lines.join("").trim().to_string();
+                if name.is_empty() {
+                    self.mode = Mode::Browse;
+                    return;
+                }
+
+                self.mode = match self.mode {
+                    Mode::Create(_) => Mode::PickColor { target: ColorTarget::Create(name), rgb: NEW_BADGE_COLOR, channel: 0 },
+                    Mode::Rename(badge_id, _) => {
+                        if store.rename_badge(badge_id, &name).is_err() {
+                            error!("couldn't rename badge {badge_id} to {name}")
+                        }
+                        Mode::Browse
+                    },
+                    _ => Mode::Browse
+                };
+            },
+            KeyCode::Char(c) => text_area.insert_char_at_cursor(c),
+            KeyCode::Backspace => text_area.delete_char_at_cursor(),
+            KeyCode::Left => text_area.move_cursor_left(),
+            KeyCode::Right => text_area.move_cursor_right(),
+            _ => {}
+        }
+    }
+
+    fn handle_color_picker(&mut self, store: &mut TaskStore, key_code: KeyCode) {
+        let Mode::PickColor { target, rgb, channel } = &mut self.mode else {
+            return
+        };
+
+        match key_code {
+            KeyCode::Esc => self.mode = Mode::Browse,
+            KeyCode::Char('j') | KeyCode::Down | KeyCode::Tab => *channel = (*channel + 1) % 3,
+            KeyCode::Char('k') | KeyCode::Up => *channel = (*channel + 2) % 3,
+            KeyCode::Char('l') | KeyCode::Right => rgb[*channel] = rgb[*channel].saturating_add(16),
+            KeyCode::Char('h') | KeyCode::Left => rgb[*channel] = rgb[*channel].saturating_sub(16),
+            KeyCode::Char('L') => rgb[*channel] = rgb[*channel].saturating_add(1),
+            KeyCode::Char('H') => rgb[*channel] = rgb[*channel].saturating_sub(1),
+            KeyCode::Enter => {
+                let color = Color::Rgb(rgb[0], rgb[1], rgb[2]);
+                match target {
+                    ColorTarget::Create(name) => match store.create_badge(name, color) {
+                        Ok(badge_id) => {
+                            self.cursor = store.sorted_badges().iter().position(|badge| badge.id == badge_id).unwrap_or(0);
+                        },
+                        Err(_) => error!("couldn't create badge {name}")
+                    },
+                    ColorTarget::Recolor(badge_id) => {
+                        if store.recolor_badge(*badge_id, color).is_err() {
+                            error!("couldn't recolor badge {badge_id}")
+                        }
+                    }
+                }
+
+                self.mode = Mode::Browse;
+            },
+            _ => {}
+        }
+    }
+
+    fn handle_transitions(&mut self, store: &mut TaskStore, key_code: KeyCode) {
+        let Mode::Transitions { badge_id, cursor } = &mut self.mode else {
+            return
+        };
+
+        let target_ids: Vec<i64> = store.sorted_badges().iter().map(|badge| badge.id).filter(|id| id != badge_id).collect();
+        let target_count = target_ids.len().max(1);
+        match key_code {
+            KeyCode::Esc | KeyCode::Char('q') => self.mode = Mode::Browse,
+            KeyCode::Char('j') | KeyCode::Down => *cursor = (*cursor + 1) % target_count,
+            KeyCode::Char('k') | KeyCode::Up => *cursor = (*cursor + target_count - 1) % target_count,
+            KeyCode::Enter | KeyCode::Char(' ') => {
+                let Some(target_id) = target_ids.get(*cursor) else {
+                    return
+                };
+
+                if let Err(err) = store.toggle_badge_transition(*badge_id, *target_id) {
+                    self.error = Some(err.to_string());
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn handle_delete(&mut self, store: &mut TaskStore, key_code: KeyCode) {
+        let Mode::Delete { badge_id, replacements, cursor } = &mut self.mode else {
+            return
+        };
+
+        match key_code {
+            KeyCode::Esc | KeyCode::Char('q') => self.mode = Mode::Browse,
+            KeyCode::Char('j') | KeyCode::Down => *cursor = (*cursor + 1) % replacements.len(),
+            KeyCode::Char('k') | KeyCode::Up => *cursor = (*cursor + replacements.len() - 1) % replacements.len(),
+            KeyCode::Enter => {
+                if let Err(err) = store.delete_badge(*badge_id, replacements[*cursor]) {
+                    self.error = Some(err.to_string());
+                }
+
+                self.cursor = self.cursor.min(store.badges.len().saturating_sub(1));
+                self.mode = Mode::Browse;
+            },
+            _ => {}
+        }
+    }
+
+    fn draw_color_picker(&self, store: &TaskStore, frame: &mut Frame, rect: Rect) {
+        let Mode::PickColor { target, rgb, channel } = &self.mode else {
+            return
+        };
+
+        let name = match target {
+            ColorTarget::Create(name) => name.clone(),
+            ColorTarget::Recolor(badge_id) => store.badges.get(badge_id).map(|badge| badge.name.clone()).unwrap_or_default()
+        };
+
+        let color = Color::Rgb(rgb[0], rgb[1], rgb[2]);
+        let mut lines = vec![
+            Line::from(vec![
+                Span::styled(name, Style::default().bg(color)),
+                Span::styled(format!("  #{:02X}{:02X}{:02X}", rgb[0], rgb[1], rgb[2]), Style::default().fg(Color::Gray))
+            ]),
+            Line::from("")
+        ];
+
+        // every channel is shown as a bar of 16 steps
+        for (idx, value) in rgb.iter().enumerate() {
+            let style = if idx == *channel {
+                Style::default().bg(Color::Rgb(20, 0, 20)).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+
+            let filled = *value as usize / 16;
+            lines.push(Line::from(vec![
+                Span::styled(format!("{:>6} {:>3} ", CHANNEL_NAMES[idx], value), style),
+                Span::styled("█".repeat(filled), style.fg(color_channel(idx, *value))),
+                Span::styled("·".repeat(15 - filled.min(15)), style.fg(Color::DarkGray))
+            ]));
+        }
+
+        frame.render_widget(Paragraph::new(lines), rect);
+    }
+}
+
+fn color_channel(channel: usize, value: u8) -> Color {
+    match channel {
+        0 => Color::Rgb(value, 0, 0),
+        1 => Color::Rgb(0, value, 0),
+        _ => Color::Rgb(0, 0, value)
+    }
+}
+
+impl Window for BadgeEditorWindow {
+    fn handle_event(&mut self, state: &mut LetterState, event: LetterEvent) -> WindowCommand {
+        let LetterEvent::RawKeyInputEvent(key_code) = event else {
+            return None
+        };
+
+        let store = &mut state.store;
+        self.error = None;
+        match self.mode {
+            Mode::Browse => return self.handle_browse(store, key_code),
+            Mode::Create(_) | Mode::Rename(_, _) => self.handle_name_input(store, key_code),
+            Mode::PickColor { .. } => self.handle_color_picker(store, key_code),
+            Mode::Transitions { .. } => self.handle_transitions(store, key_code),
+            Mode::Delete { .. } => self.handle_delete(store, key_code)
+        }
+
+        None
+    }
+
+    fn update(&mut self, _state: &mut LetterState) -> WindowCommand {
+        None
+    }
+
+    fn draw(&self, state: &LetterState, frame: &mut Frame, rect: Rect) {
+        let store = &state.store;
+        let badges = store.sorted_badges();
+
+        let content_height = match &self.mode {
+            Mode::PickColor { .. } => 5,
+            Mode::Transitions { .. } => badges.len() as u16,
+            Mode::Delete { replacements, .. } => replacements.len() as u16 + 1,
+            _ => badges.len() as u16
+        };
+
+        let rect = popup_rect(48, content_height.max(1) + 3, rect);
+        let block = Block::default()
+            .title("Badges")
+            .borders(Borders::ALL);
+
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(1),
+                Constraint::Length(1)
+            ]).split(block.inner(rect));
+
+        frame.render_widget(Clear, rect);
+        frame.render_widget(block, rect);
+
+        let hint = match &self.mode {
+            Mode::Browse => {
+                let list_items: Vec<ListItem> = badges.iter()
+                    .enumerate()
+                    .map(|(idx, badge)| {
+                        let marker = if idx == self.cursor { "> " } else { "  " };
+                        let style = if idx == self.cursor { Style::default().bg(Color::Rgb(20, 0, 20)) } else { Style::default() };
+
+                        // the badges that may follow it, if that's restricted
+                        let next_names: Vec<String> = store.next_badge_ids(badge.id).iter()
+                            .filter(|_| store.has_transitions(badge.id))
+                            .filter_map(|id| store.badges.get(id))
+                            .map(|next| next.name.clone())
+                            .collect();
+                        let details = match (badge.done, next_names.is_empty()) {
+                            (true, true) => " done".to_string(),
+                            (true, false) => format!(" done → {}", next_names.join(", ")),
+                            (false, true) => String::new(),
+                            (false, false) => format!(" → {}", next_names.join(", "))
+                        };
+
+                        ListItem::new(Line::from(vec![
+                            Span::styled(marker, style),
+                            Span::styled(badge.name.clone(), Style::default().bg(badge.color)),
+                            Span::styled(details, style.fg(Color::DarkGray))
+                        ])).style(style)
+                    })
+                    .collect();
+                frame.render_widget(List::new(list_items), layout[0]);
+
+                if self.pending_delete {
+                    "press d again to delete the badge"
+                } else {
+                    "n new, r rename, c color, x done, a next, J/K move, dd delete"
+                }
+            },
+            Mode::Create(text_area) | Mode::Rename(_, text_area) => {
+                frame.render_widget(Paragraph::new("name:"), layout[0]);
+                text_area.draw(frame, layout[1]);
+                return;
+            },
+            Mode::PickColor { .. } => {
+                self.draw_color_picker(store, frame, layout[0]);
+                "j/k channel, h/l ±16, H/L ±1, enter save"
+            },
+            Mode::Transitions { badge_id, cursor } => {
+                let name = store.badges.get(badge_id).map(|badge| badge.name.clone()).unwrap_or_default();
+                let mut list_items = vec![ListItem::new(format!("'{name}' may be followed by:"))];
+                list_items.extend(badges.iter()
+                    .filter(|badge| badge.id != *badge_id)
+                    .enumerate()
+                    .map(|(idx, badge)| {
+                        let check = if store.is_transition(*badge_id, badge.id) { 'x' } else { ' ' };
+                        if idx == *cursor {
+                            return ListItem::new(format!("> [{check}] {}", badge.name)).style(Style::default().bg(Color::Rgb(20, 0, 20)));
+                        }
+                        ListItem::new(format!("  [{check}] {}", badge.name))
+                    }));
+                frame.render_widget(List::new(list_items), layout[0]);
+
+                "enter toggle, none picked allows any, esc back"
+            },
+            Mode::Delete { badge_id, replacements, cursor } => {
+                let name = store.badges.get(badge_id).map(|badge| badge.name.clone()).unwrap_or_default();
+                let mut list_items = vec![ListItem::new(format!("tasks with '{name}' get:"))];
+                list_items.extend(replacements.iter()
+                    .enumerate()
+                    .map(|(idx, replacement)| {
+                        let name = replacement
+                            .and_then(|id| store.badges.get(&id))
+                            .map(|badge| badge.name.clone())
+                            .unwrap_or_else(|| "no badge".to_string());
+
+                        if idx == *cursor {
+                            return ListItem::new(format!("> {name}")).style(Style::default().bg(Color::Rgb(20, 0, 20)));
+                        }
+                        ListItem::new(format!("  {name}"))
+                    }));
+                frame.render_widget(List::new(list_items), layout[0]);
+
+                "enter delete, esc cancel"
+            }
+        };
+
+        let footer_paragraph = match &self.error {
+            Some(error) => Paragraph::new(error.clone()).style(Style::default().fg(Color::LightRed)),
+            None if self.pending_delete => Paragraph::new(hint).style(Style::default().fg(Color::LightYellow)),
+            None => Paragraph::new(hint).style(Style::default().fg(Color::DarkGray))
+        };
+        frame.render_widget(footer_paragraph, layout[1]);
+    }
+
+    fn is_popup(&self) -> bool {
+        true
+    }
+}
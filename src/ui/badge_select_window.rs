@@ -0,0 +1,120 @@
+use crossterm::event::KeyCode;
+use log::error;
+use ratatui::{prelude::Rect, widgets::{Block, Borders, Clear, List, ListItem}, style::{Style, Color}};
+
+use crate::{dates, store::TaskStore, ui::popup_rect, Frame, LetterEvent, LetterState, Window, WindowCommand, _WindowCommand};
+
+pub struct BadgeSelectWindow {
+    task_id: i64,
+    cursor: usize,
+    // badge ids in display order, None removes the badge
+    values: Vec<Option<i64>>
+}
+
+impl BadgeSelectWindow {
+    pub fn new(store: &TaskStore, task_id: i64) -> Self {
+        let mut values: Vec<Option<i64>> = store.sorted_badges().iter().map(|badge| Some(badge.id)).collect();
+        values.push(None);
+
+        // starts on the badge the task has
+        let badge_id = store.tasks.iter().find(|task| task.id == Some(task_id)).and_then(|task| task.badge_id);
+        let cursor = values.iter().position(|value| *value == badge_id).unwrap_or(0);
+
+        Self { task_id, cursor, values }
+    }
+
+    fn apply(&self, state: &mut LetterState) {
+        let task_id = self.task_id;
+        let result = match self.values[self.cursor] {
+            Some(badge_id) => state.store.update_task_badge(task_id, badge_id),
+            None => state.store.unset_task_badge(task_id).map(|_| None)
+        };
+
+        match result {
+            Ok(Some(next_id)) => {
+                let due_date = state.store.tasks.iter()
+                    .find(|task| task.id == Some(next_id))
+                    .and_then(|task| task.due_date)
+                    .map(|due_date| format!(", due {}", dates::format_date(due_date, dates::today())))
+                    .unwrap_or_default();
+
+                state.message = Some(format!("created the next occurrence{due_date}"));
+                state.focus_task_id = Some(next_id);
+            },
+            Ok(None) => state.focus_task_id = Some(task_id),
+            Err(err) => {
+                error!("couldn't update the badge of task {task_id}: {err}");
+                state.message = Some(err.to_string());
+            }
+        }
+    }
+}
+
+impl Window for BadgeSelectWindow {
+    fn handle_event(&mut self, state: &mut LetterState, event: LetterEvent) -> WindowCommand {
+        let LetterEvent::RawKeyInputEvent(key_code) = event else {
+            return None
+        };
+
+        let value_count = self.values.len();
+        match key_code {
+            KeyCode::Char('j') | KeyCode::Down => self.cursor = (self.cursor + 1) % value_count,
+            KeyCode::Char('k') | KeyCode::Up => self.cursor = (self.cursor + value_count - 1) % value_count,
+            KeyCode::Enter => {
+                self.apply(state);
+                return Some(_WindowCommand::Quit);
+            },
+            KeyCode::Esc | KeyCode::Char('q' | 't') => return Some(_WindowCommand::Quit),
+            _ => {}
+        }
+
+        None
+    }
+
+    fn update(&mut self, _state: &mut LetterState) -> WindowCommand {
+        None
+    }
+
+    fn draw(&self, state: &LetterState, frame: &mut Frame, rect: Rect) {
+        let store = &state.store;
+        let current_id = store.tasks.iter().find(|task| task.id == Some(self.task_id)).and_then(|task| task.badge_id);
+
+        let names: Vec<String> = self.values.iter()
+            .map(|value| match value.and_then(|badge_id| store.badges.get(&badge_id)) {
+                Some(badge) if badge.done => format!("{} ✓", badge.name),
+                Some(badge) => badge.name.clone(),
+                None => "none".to_string()
+            })
+            .collect();
+
+        let width = names.iter().map(|name| name.chars().count()).max().unwrap_or(0) as u16 + 6;
+        let rect = popup_rect(width.max(16), names.len() as u16 + 2, rect);
+        let block = Block::default()
+            .title("Badge")
+            .borders(Borders::ALL);
+
+        let list_items: Vec<ListItem> = names.into_iter()
+            .enumerate()
+            .map(|(idx, name)| {
+                // badges the transitions don't allow are dimmed
+                let color = match self.values[idx] {
+                    Some(badge_id) if !store.can_change_badge(current_id, badge_id) => Color::DarkGray,
+                    Some(badge_id) => store.badges.get(&badge_id).map(|badge| badge.color).unwrap_or(Color::DarkGray),
+                    None => Color::DarkGray
+                };
+
+                match idx == self.cursor {
+                    true => ListItem::new(format!("> {name}")).style(Style::default().fg(color).bg(Color::Rgb(20, 0, 20))),
+                    false => ListItem::new(format!("  {name}")).style(Style::default().fg(color))
+                }
+            })
+            .collect();
+
+        frame.render_widget(Clear, rect);
+        frame.render_widget(List::new(list_items).block(block), rect);
+    }
+
+    fn is_popup(&self) -> bool {
+        true
+    }
+}
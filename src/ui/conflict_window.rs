@@ -0,0 +1,96 @@
+use crossterm::event::KeyCode;
+use log::error;
+use ratatui::{prelude::{Rect, Layout, Direction, Constraint}, widgets::{Block, Borders, Clear, Paragraph}, style::{Style, Color}};
+
+use crate::{store::Task, ui::popup_rect, Frame, LetterEvent, LetterState, Window, WindowCommand, _WindowCommand};
+
+// Opened when an edited line couldn't be written because another process changed the task
+// in the meantime. The store holds their version already, the edit waits here until the user
+// picks one of them.
+pub struct ConflictWindow {
+    task_id: i64,
+    mine: String
+}
+
+impl ConflictWindow {
+    pub fn new(task_id: i64, mine: String) -> Self {
+        Self { task_id, mine }
+    }
+
+    fn keep_mine(&self, state: &mut LetterState) {
+        let store = &mut state.store;
+        let exists = store.tasks.iter().any(|task| task.id == Some(self.task_id));
+
+        // a task deleted elsewhere comes back as a new one
+        let result = match exists {
+            true => store.update_task_text(self.task_id, &self.mine).map(|_| self.task_id),
+            false => store.create_task(Task { text: self.mine.clone(), ..Default::default() })
+        };
+
+        match result {
+            Ok(task_id) => state.focus_task_id = Some(task_id),
+            Err(err) => {
+                error!("couldn't keep the edit of task {}: {err}", self.task_id);
+                state.message = Some(err.to_string());
+            }
+        }
+    }
+}
+
+impl Window for ConflictWindow {
+    fn handle_event(&mut self, state: &mut LetterState, event: LetterEvent) -> WindowCommand {
+        let LetterEvent::RawKeyInputEvent(key_code) = event else {
+            return None
+        };
+
+        match key_code {
+            KeyCode::Char('m') => {
+                self.keep_mine(state);
+                Some(_WindowCommand::Quit)
+            },
+            KeyCode::Char('t') | KeyCode::Esc => Some(_WindowCommand::Quit),
+            _ => None
+        }
+    }
+
+    fn update(&mut self, _state: &mut LetterState) -> WindowCommand {
+        None
+    }
+
+    fn draw(&self, state: &LetterState, frame: &mut Frame, rect: Rect) {
+        let theirs = state.store.tasks.iter()
+            .find(|task| task.id == Some(self.task_id))
+            .map(|task| task.text.as_str());
+
+        let rect = popup_rect(64, 6, rect);
+        let block = Block::default()
+            .title("Changed elsewhere")
+            .borders(Borders::ALL);
+
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(1),
+                Constraint::Length(1),
+                Constraint::Length(1),
+                Constraint::Length(1)
+            ]).split(block.inner(rect));
+
+        let theirs_paragraph = match theirs {
+            Some(text) => Paragraph::new(format!("theirs: {text}")),
+            None => Paragraph::new("theirs: deleted").style(Style::default().fg(Color::LightRed))
+        };
+        let mine_paragraph = Paragraph::new(format!("mine:   {}", self.mine));
+        let hint_paragraph = Paragraph::new("m keep mine, t take theirs").style(Style::default().fg(Color::DarkGray));
+
+        frame.render_widget(Clear, rect);
+        frame.render_widget(block, rect);
+        frame.render_widget(theirs_paragraph, layout[0]);
+        frame.render_widget(mine_paragraph, layout[1]);
+        frame.render_widget(hint_paragraph, layout[3]);
+    }
+
+    fn is_popup(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,127 @@
+use crossterm::event::KeyCode;
+use log::error;
+use ratatui::{prelude::{Rect, Layout, Direction, Constraint}, widgets::{Block, Borders, Clear, Paragraph}, style::{Style, Color}};
+
+use crate::{dates, store::TaskStore, ui::{textarea::TextArea, popup_rect}, Frame, LetterCommand, LetterEvent, LetterState, Window, WindowCommand, _WindowCommand};
+
+#[derive(Clone, Copy)]
+pub enum DateField {
+    Due,
+    Start
+}
+
+pub struct DateInputWindow {
+    task_id: i64,
+    field: DateField,
+    text_area: TextArea<LetterState, LetterCommand>,
+    error: Option<String>
+}
+
+impl DateInputWindow {
+    pub fn new(store: &TaskStore, task_id: i64, field: DateField) -> Self {
+        let task = store.tasks.iter().find(|task| task.id == Some(task_id));
+        let date = task.and_then(|task| match field {
+            DateField::Due => task.due_date,
+            DateField::Start => task.start_date
+        });
+
+        let mut text_area = TextArea::new(vec![date.map(|date| date.to_string()).unwrap_or_default()]);
+        text_area.disallow_line_breaks();
+        text_area.move_cursor_to_line_end();
+
+        Self {
+            task_id,
+            field,
+            text_area,
+            error: None
+        }
+    }
+
+    fn apply(&mut self, state: &mut LetterState) -> WindowCommand {
+        let input = self.text_area.lines.join("");
+        let input = input.trim();
+
+        // an empty input removes the date
+        let date = if input.is_empty() {
+            None
+        } else {
+            match dates::parse_date(input, dates::today()) {
+                Some(date) => Some(date),
+                None => {
+                    self.error = Some(format!("unknown date '{input}'"));
+                    return None;
+                }
+            }
+        };
+
+        let task_id = self.task_id;
+        let result = match self.field {
+            DateField::Due => state.store.update_task_due_date(task_id, date),
+            DateField::Start => state.store.update_task_start_date(task_id, date)
+        };
+
+        if result.is_err() {
+            error!("couldn't update date of task {task_id}")
+        }
+
+        Some(_WindowCommand::Quit)
+    }
+}
+
+impl Window for DateInputWindow {
+    fn handle_event(&mut self, state: &mut LetterState, event: LetterEvent) -> WindowCommand {
+        let LetterEvent::RawKeyInputEvent(key_code) = event else {
+            return None
+        };
+
+        self.error = None;
+        match key_code {
+            KeyCode::Esc => return Some(_WindowCommand::Quit),
+            KeyCode::Enter => return self.apply(state),
+            KeyCode::Char(c) => self.text_area.insert_char_at_cursor(c),
+            KeyCode::Backspace => self.text_area.delete_char_at_cursor(),
+            KeyCode::Left => self.text_area.move_cursor_left(),
+            KeyCode::Right => self.text_area.move_cursor_right(),
+            _ => {}
+        }
+
+        None
+    }
+
+    fn update(&mut self, _state: &mut LetterState) -> WindowCommand {
+        None
+    }
+
+    fn draw(&self, _state: &LetterState, frame: &mut Frame, rect: Rect) {
+        let title = match self.field {
+            DateField::Due => "Due date",
+            DateField::Start => "Start date"
+        };
+
+        let rect = popup_rect(48, 4, rect);
+        let block = Block::default()
+            .title(title)
+            .borders(Borders::ALL);
+
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(1),
+                Constraint::Length(1)
+            ]).split(block.inner(rect));
+
+        let hint_paragraph = match &self.error {
+            Some(error) => Paragraph::new(error.clone()).style(Style::default().fg(Color::LightRed)),
+            None => Paragraph::new("2024-05-01, today, fri, +3d, empty to clear").style(Style::default().fg(Color::DarkGray))
+        };
+
+        frame.render_widget(Clear, rect);
+        frame.render_widget(block, rect);
+        frame.render_widget(hint_paragraph, layout[1]);
+        self.text_area.draw(frame, layout[0]);
+    }
+
+    fn is_popup(&self) -> bool {
+        true
+    }
+}
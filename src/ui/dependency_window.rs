@@ -0,0 +1,226 @@
+use crossterm::event::KeyCode;
+use ratatui::{prelude::{Rect, Layout, Direction, Constraint}, widgets::{Block, Borders, Clear, List, ListItem, Paragraph}, style::{Style, Color, Modifier}};
+
+use crate::{store::TaskStore, ui::popup_rect, Frame, LetterEvent, LetterState, Window, WindowCommand, _WindowCommand};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Relation {
+    // the selected task blocks ours
+    Blocker,
+    // our task blocks the selected one
+    Blocked
+}
+
+enum Mode {
+    Browse,
+    // picks a task of the list as another blocker
+    AddBlocker {
+        candidate_ids: Vec<i64>,
+        cursor: usize
+    }
+}
+
+pub struct DependencyWindow {
+    task_id: i64,
+    cursor: usize,
+    mode: Mode,
+    error: Option<String>
+}
+
+impl DependencyWindow {
+    pub fn new(task_id: i64) -> Self {
+        Self {
+            task_id,
+            cursor: 0,
+            mode: Mode::Browse,
+            error: None
+        }
+    }
+
+    // blockers first, then the tasks waiting for ours
+    fn rows(&self, store: &TaskStore) -> Vec<(Relation, i64)> {
+        let blockers = store.blockers(self.task_id).into_iter().map(|task| (Relation::Blocker, task));
+        let blocked = store.blocked_tasks(self.task_id).into_iter().map(|task| (Relation::Blocked, task));
+
+        blockers.chain(blocked)
+            .filter_map(|(relation, task)| Some((relation, task.id?)))
+            .collect()
+    }
+
+    fn handle_browse(&mut self, state: &mut LetterState, key_code: KeyCode) -> WindowCommand {
+        let rows = self.rows(&state.store);
+        let row_count = rows.len().max(1);
+        match key_code {
+            KeyCode::Char('j') | KeyCode::Down => self.cursor = (self.cursor + 1) % row_count,
+            KeyCode::Char('k') | KeyCode::Up => self.cursor = (self.cursor + row_count - 1) % row_count,
+            KeyCode::Char('a') => {
+                let store = &state.store;
+                let blocker_ids: Vec<Option<i64>> = store.blockers(self.task_id).iter().map(|task| task.id).collect();
+                let candidate_ids = store.tasks.iter()
+                    .filter(|task| task.id != Some(self.task_id) && !blocker_ids.contains(&task.id))
+                    .filter_map(|task| task.id)
+                    .collect();
+
+                self.mode = Mode::AddBlocker { candidate_ids, cursor: 0 };
+            },
+            KeyCode::Char('d') => {
+                let (relation, other_id) = *rows.get(self.cursor)?;
+                let result = match relation {
+                    Relation::Blocker => state.store.remove_blocker(self.task_id, other_id),
+                    Relation::Blocked => state.store.remove_blocker(other_id, self.task_id)
+                };
+
+                match result {
+                    Ok(()) => self.cursor = self.cursor.min(rows.len().saturating_sub(2)),
+                    Err(err) => self.error = Some(err.to_string())
+                }
+            },
+            KeyCode::Enter => {
+                let (_, other_id) = *rows.get(self.cursor)?;
+                state.focus_task_id = Some(other_id);
+                return Some(_WindowCommand::Quit);
+            },
+            KeyCode::Esc | KeyCode::Char('q') => return Some(_WindowCommand::Quit),
+            _ => {}
+        }
+
+        None
+    }
+
+    fn handle_add_blocker(&mut self, state: &mut LetterState, key_code: KeyCode) {
+        let Mode::AddBlocker { candidate_ids, cursor } = &mut self.mode else {
+            return
+        };
+
+        let candidate_count = candidate_ids.len().max(1);
+        match key_code {
+            KeyCode::Char('j') | KeyCode::Down => *cursor = (*cursor + 1) % candidate_count,
+            KeyCode::Char('k') | KeyCode::Up => *cursor = (*cursor + candidate_count - 1) % candidate_count,
+            KeyCode::Enter => {
+                let Some(blocker_id) = candidate_ids.get(*cursor).copied() else {
+                    return
+                };
+
+                match state.store.add_blocker(self.task_id, blocker_id) {
+                    Ok(()) => self.mode = Mode::Browse,
+                    Err(err) => self.error = Some(err.to_string())
+                }
+            },
+            KeyCode::Esc => self.mode = Mode::Browse,
+            _ => {}
+        }
+    }
+}
+
+fn task_text(store: &TaskStore, task_id: i64) -> String {
+    store.tasks.iter()
+        .find(|task| task.id == Some(task_id))
+        .map(|task| task.text.clone())
+        .unwrap_or_default()
+}
+
+fn item(text: String, selected: bool, style: Style) -> ListItem<'static> {
+    match selected {
+        true => ListItem::new(format!("> {text}")).style(style.bg(Color::Rgb(20, 0, 20))),
+        false => ListItem::new(format!("  {text}")).style(style)
+    }
+}
+
+impl Window for DependencyWindow {
+    fn handle_event(&mut self, state: &mut LetterState, event: LetterEvent) -> WindowCommand {
+        let LetterEvent::RawKeyInputEvent(key_code) = event else {
+            return None
+        };
+
+        self.error = None;
+        match self.mode {
+            Mode::Browse => return self.handle_browse(state, key_code),
+            Mode::AddBlocker { .. } => self.handle_add_blocker(state, key_code)
+        }
+
+        None
+    }
+
+    fn update(&mut self, state: &mut LetterState) -> WindowCommand {
+        // the task may be gone after a reload
+        match state.store.tasks.iter().any(|task| task.id == Some(self.task_id)) {
+            true => None,
+            false => Some(_WindowCommand::Quit)
+        }
+    }
+
+    fn draw(&self, state: &LetterState, frame: &mut Frame, rect: Rect) {
+        let store = &state.store;
+        let dim = Style::default().fg(Color::DarkGray);
+
+        let (title, list_items, selected_line, hint) = match &self.mode {
+            Mode::Browse => {
+                let rows = self.rows(store);
+                let mut list_items = vec![];
+                let mut selected_line = 0;
+                for relation in [Relation::Blocker, Relation::Blocked] {
+                    let heading = match relation {
+                        Relation::Blocker => "blocked by",
+                        Relation::Blocked => "blocks"
+                    };
+                    list_items.push(ListItem::new(heading).style(dim.add_modifier(Modifier::BOLD)));
+
+                    let before = list_items.len();
+                    for (idx, (_, other_id)) in rows.iter().enumerate().filter(|(_, (other, _))| *other == relation) {
+                        let done = store.tasks.iter().find(|task| task.id == Some(*other_id)).is_some_and(|task| store.is_done(task));
+                        let style = match done {
+                            true => dim.add_modifier(Modifier::CROSSED_OUT),
+                            false => Style::default()
+                        };
+                        if idx == self.cursor {
+                            selected_line = list_items.len();
+                        }
+                        list_items.push(item(task_text(store, *other_id), idx == self.cursor, style));
+                    }
+                    if list_items.len() == before {
+                        list_items.push(ListItem::new("  nothing").style(dim));
+                    }
+                }
+
+                let title = format!("Dependencies of {}", task_text(store, self.task_id));
+                (title, list_items, selected_line, "a add blocker, d remove, enter go to task")
+            },
+            Mode::AddBlocker { candidate_ids, cursor } => {
+                let list_items = candidate_ids.iter()
+                    .enumerate()
+                    .map(|(idx, task_id)| item(task_text(store, *task_id), idx == *cursor, Style::default()))
+                    .collect();
+
+                ("Blocked by".to_string(), list_items, *cursor, "enter add, esc back")
+            }
+        };
+
+        let rect = popup_rect(60, list_items.len().clamp(1, 16) as u16 + 3, rect);
+        let block = Block::default()
+            .title(title)
+            .borders(Borders::ALL);
+
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(1),
+                Constraint::Length(1)
+            ]).split(block.inner(rect));
+
+        let footer_paragraph = match &self.error {
+            Some(error) => Paragraph::new(error.clone()).style(Style::default().fg(Color::LightRed)),
+            None => Paragraph::new(hint).style(dim)
+        };
+
+        frame.render_widget(Clear, rect);
+        frame.render_widget(block, rect);
+        // keeps the selected task visible once the tasks don't fit anymore
+        let scroll = (selected_line + 1).saturating_sub(layout[0].height as usize);
+        frame.render_widget(List::new(list_items.into_iter().skip(scroll).collect::<Vec<_>>()), layout[0]);
+        frame.render_widget(footer_paragraph, layout[1]);
+    }
+
+    fn is_popup(&self) -> bool {
+        true
+    }
+}
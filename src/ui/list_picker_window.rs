@@ -0,0 +1,195 @@
+use crossterm::event::KeyCode;
+use log::error;
+use ratatui::{prelude::{Rect, Layout, Direction, Constraint}, widgets::{Block, Borders, Clear, List, ListItem, Paragraph}, style::{Style, Color, Modifier}};
+
+use crate::{store::TaskStore, ui::{textarea::TextArea, popup_rect}, Frame, LetterCommand, LetterEvent, LetterState, Window, WindowCommand, _WindowCommand};
+
+#[derive(Clone, Copy)]
+pub enum ListPickerAction {
+    Switch,
+    MoveTask(i64)
+}
+
+#[derive(Clone, Copy)]
+enum NameInput {
+    Create,
+    Rename(i64)
+}
+
+pub struct ListPickerWindow {
+    action: ListPickerAction,
+    cursor: usize,
+    name_input: Option<(NameInput, TextArea<LetterState, LetterCommand>)>,
+    pending_delete: bool,
+    error: Option<String>
+}
+
+impl ListPickerWindow {
+    pub fn new(store: &TaskStore, action: ListPickerAction) -> Self {
+        let cursor = store.current_list()
+            .and_then(|current_list| store.lists.iter().position(|list| list.id == current_list.id))
+            .unwrap_or(0);
+
+        Self {
+            action,
+            cursor,
+            name_input: None,
+            pending_delete: false,
+            error: None
+        }
+    }
+
+    fn start_name_input(&mut self, name_input: NameInput, name: &str) {
+        let mut text_area = TextArea::new(vec![String::from(name)]);
+        text_area.disallow_line_breaks();
+        text_area.move_cursor_to_line_end();
+
+        self.name_input = Some((name_input, text_area));
+    }
+
+    fn handle_name_input(&mut self, store: &mut TaskStore, key_code: KeyCode) {
+        let Some((name_input, text_area)) = &mut self.name_input else {
+            return
+        };
+
+        match key_code {
+            KeyCode::Esc => self.name_input = None,
+            KeyCode::Enter => {
+                let name = text_area.lines.join("").trim().to_string();
+                if !name.is_empty() {
+                    match *name_input {
+                        NameInput::Create => match store.create_list(&name) {
+                            Ok(_) => self.cursor = store.lists.len() - 1,
+                            Err(_) => error!("couldn't create list {name}")
+                        },
+                        NameInput::Rename(list_id) => {
+                            if store.rename_list(list_id, &name).is_err() {
+                                error!("couldn't rename list {list_id} to {name}")
+                            }
+                        }
+                    }
+                }
+
+                self.name_input = None;
+            },
+            KeyCode::Char(c) => text_area.insert_char_at_cursor(c),
+            KeyCode::Backspace => text_area.delete_char_at_cursor(),
+            KeyCode::Left => text_area.move_cursor_left(),
+            KeyCode::Right => text_area.move_cursor_right(),
+            _ => {}
+        }
+    }
+}
+
+impl Window for ListPickerWindow {
+    fn handle_event(&mut self, state: &mut LetterState, event: LetterEvent) -> WindowCommand {
+        let LetterEvent::RawKeyInputEvent(key_code) = event else {
+            return None
+        };
+
+        let store = &mut state.store;
+        self.error = None;
+        if self.name_input.is_some() {
+            self.handle_name_input(store, key_code);
+            return None;
+        }
+
+        let pending_delete = std::mem::take(&mut self.pending_delete);
+        let list_count = store.lists.len();
+        let list_id = store.lists[self.cursor].id;
+        match key_code {
+            KeyCode::Char('j') | KeyCode::Down => self.cursor = (self.cursor + 1) % list_count,
+            KeyCode::Char('k') | KeyCode::Up => self.cursor = (self.cursor + list_count - 1) % list_count,
+            KeyCode::Enter => {
+                let result = match self.action {
+                    ListPickerAction::Switch => store.switch_list(list_id),
+                    ListPickerAction::MoveTask(task_id) => store.move_task_to_list(task_id, list_id)
+                };
+
+                if result.is_err() {
+                    error!("couldn't use list {list_id}")
+                }
+
+                return Some(_WindowCommand::Quit);
+            },
+            KeyCode::Char('n') => self.start_name_input(NameInput::Create, ""),
+            KeyCode::Char('r') => {
+                let name = store.lists[self.cursor].name.clone();
+                self.start_name_input(NameInput::Rename(list_id), &name);
+            },
+            KeyCode::Char('d') if pending_delete => {
+                if let Err(err) = store.delete_list(list_id) {
+                    self.error = Some(err.to_string());
+                }
+                self.cursor = self.cursor.min(store.lists.len() - 1);
+            },
+            KeyCode::Char('d') => self.pending_delete = true,
+            KeyCode::Esc | KeyCode::Char('q') => return Some(_WindowCommand::Quit),
+            _ => {}
+        }
+
+        None
+    }
+
+    fn update(&mut self, _state: &mut LetterState) -> WindowCommand {
+        None
+    }
+
+    fn draw(&self, state: &LetterState, frame: &mut Frame, rect: Rect) {
+        let store = &state.store;
+        let title = match self.action {
+            ListPickerAction::Switch => "Lists",
+            ListPickerAction::MoveTask(_) => "Move task to"
+        };
+
+        let rect = popup_rect(48, store.lists.len() as u16 + 3, rect);
+        let block = Block::default()
+            .title(title)
+            .borders(Borders::ALL);
+
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(1),
+                Constraint::Length(1)
+            ]).split(block.inner(rect));
+
+        let current_list_id = store.current_list().map(|list| list.id);
+        let list_items: Vec<ListItem> = store.lists.iter()
+            .enumerate()
+            .map(|(idx, list)| {
+                let style = if Some(list.id) == current_list_id {
+                    Style::default().add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+
+                if idx == self.cursor {
+                    return ListItem::new(format!("> {}", list.name)).style(style.bg(Color::Rgb(20, 0, 20)));
+                }
+                ListItem::new(format!("  {}", list.name)).style(style)
+            })
+            .collect();
+
+        frame.render_widget(Clear, rect);
+        frame.render_widget(block, rect);
+        frame.render_widget(List::new(list_items), layout[0]);
+
+        if let Some((_, text_area)) = &self.name_input {
+            text_area.draw(frame, layout[1]);
+            return;
+        }
+
+        let footer_paragraph = match (&self.error, self.pending_delete) {
+            (Some(error), _) => Paragraph::new(error.clone()).style(Style::default().fg(Color::LightRed)),
+            (None, true) => Paragraph::new(format!("press d again to delete '{}' and its tasks", store.lists[self.cursor].name))
+                .style(Style::default().fg(Color::LightYellow)),
+            (None, false) => Paragraph::new("n new, r rename, dd delete").style(Style::default().fg(Color::DarkGray))
+        };
+        frame.render_widget(footer_paragraph, layout[1]);
+    }
+
+    fn is_popup(&self) -> bool {
+        true
+    }
+}
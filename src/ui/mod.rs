@@ -1,2 +1,22 @@
 // pub mod panel;
 pub mod textarea;
+pub mod date_input_window;
+pub mod list_picker_window;
+pub mod search_window;
+pub mod badge_editor_window;
+pub mod conflict_window;
+pub mod trash_window;
+pub mod task_detail_window;
+pub mod sort_window;
+pub mod badge_select_window;
+pub mod recurrence_input_window;
+pub mod dependency_window;
+
+use ratatui::prelude::Rect;
+
+pub fn popup_rect(width: u16, height: u16, area: Rect) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+
+    Rect::new(area.x + (area.width - width) / 2, area.y + (area.height - height) / 2, width, height)
+}
@@ -0,0 +1,162 @@
+use crossterm::event::KeyCode;
+use log::error;
+use ratatui::{prelude::{Rect, Layout, Direction, Constraint}, widgets::{Block, Borders, Clear, Paragraph}, style::{Style, Color, Modifier}, text::{Line, Span}};
+
+use crate::{store::{SearchHit, MATCH_START, MATCH_END}, ui::{textarea::TextArea, popup_rect}, Frame, LetterCommand, LetterEvent, LetterState, Window, WindowCommand, _WindowCommand};
+
+const MAX_HITS: usize = 50;
+
+pub struct SearchWindow {
+    text_area: TextArea<LetterState, LetterCommand>,
+    hits: Vec<SearchHit>,
+    cursor: usize
+}
+
+impl SearchWindow {
+    pub fn new() -> Self {
+        let mut text_area = TextArea::new(vec![]);
+        text_area.disallow_line_breaks();
+
+        Self {
+            text_area,
+            hits: vec![],
+            cursor: 0
+        }
+    }
+
+    fn run_search(&mut self, state: &LetterState) {
+        let input = self.text_area.lines.join("");
+        self.cursor = 0;
+        self.hits = match state.store.search(&input, MAX_HITS) {
+            Ok(hits) => hits,
+            Err(err) => {
+                error!("search for '{input}' failed: {err}");
+                vec![]
+            }
+        };
+    }
+
+    fn jump_to_hit(&self, state: &mut LetterState) {
+        let Some(hit) = self.hits.get(self.cursor) else {
+            return
+        };
+
+        let in_current_list = state.store.current_list().is_some_and(|list| list.id == hit.list_id);
+        if !in_current_list && state.store.switch_list(hit.list_id).is_err() {
+            error!("couldn't switch to list {}", hit.list_id);
+            return;
+        }
+
+        state.focus_task_id = Some(hit.task_id);
+    }
+}
+
+fn highlighted_line(text: &str, style: Style) -> Line<'static> {
+    let match_style = style.fg(Color::LightYellow).add_modifier(Modifier::BOLD);
+
+    let mut spans = vec![];
+    let mut is_match = false;
+    let mut current = String::new();
+    for c in text.chars() {
+        if c != MATCH_START && c != MATCH_END {
+            current.push(c);
+            continue;
+        }
+
+        spans.push(Span::styled(std::mem::take(&mut current), if is_match { match_style } else { style }));
+        is_match = c == MATCH_START;
+    }
+    spans.push(Span::styled(current, if is_match { match_style } else { style }));
+
+    Line::from(spans)
+}
+
+impl Window for SearchWindow {
+    fn handle_event(&mut self, state: &mut LetterState, event: LetterEvent) -> WindowCommand {
+        let LetterEvent::RawKeyInputEvent(key_code) = event else {
+            return None
+        };
+
+        match key_code {
+            KeyCode::Esc => return Some(_WindowCommand::Quit),
+            KeyCode::Enter => {
+                self.jump_to_hit(state);
+                return Some(_WindowCommand::Quit);
+            },
+            KeyCode::Down if !self.hits.is_empty() => self.cursor = (self.cursor + 1) % self.hits.len(),
+            KeyCode::Up if !self.hits.is_empty() => self.cursor = (self.cursor + self.hits.len() - 1) % self.hits.len(),
+            KeyCode::Left => self.text_area.move_cursor_left(),
+            KeyCode::Right => self.text_area.move_cursor_right(),
+            KeyCode::Char(c) => {
+                self.text_area.insert_char_at_cursor(c);
+                self.run_search(state);
+            },
+            KeyCode::Backspace => {
+                self.text_area.delete_char_at_cursor();
+                self.run_search(state);
+            },
+            _ => {}
+        }
+
+        None
+    }
+
+    fn update(&mut self, _state: &mut LetterState) -> WindowCommand {
+        None
+    }
+
+    fn draw(&self, state: &LetterState, frame: &mut Frame, rect: Rect) {
+        let rect = popup_rect(rect.width * 7 / 10, rect.height * 8 / 10, rect);
+        let block = Block::default()
+            .title("Search")
+            .borders(Borders::ALL);
+
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(1),
+                Constraint::Length(1),
+                Constraint::Min(0)
+            ]).split(block.inner(rect));
+
+        frame.render_widget(Clear, rect);
+        frame.render_widget(block, rect);
+        self.text_area.draw(frame, layout[0]);
+
+        let current_list_id = state.store.current_list().map(|list| list.id);
+        let mut lines: Vec<Line> = vec![];
+        let mut cursor_line = 0;
+        for (idx, hit) in self.hits.iter().enumerate() {
+            let style = if idx == self.cursor {
+                cursor_line = lines.len();
+                Style::default().bg(Color::Rgb(20, 0, 20))
+            } else {
+                Style::default()
+            };
+
+            let mut line = highlighted_line(&hit.text, style);
+            if Some(hit.list_id) != current_list_id {
+                let list_name = state.store.lists.iter()
+                    .find(|list| list.id == hit.list_id)
+                    .map(|list| list.name.clone())
+                    .unwrap_or_default();
+                line.spans.push(Span::styled(format!("  ({list_name})"), style.fg(Color::DarkGray)));
+            }
+            lines.push(line);
+
+            if !hit.snippet.is_empty() {
+                let snippet = format!("    {}", hit.snippet.replace('\n', " "));
+                lines.push(highlighted_line(&snippet, style.fg(Color::Gray)));
+            }
+        }
+
+        // keeps the selected hit visible once the hits don't fit anymore
+        let height = layout[2].height as usize;
+        let scroll = (cursor_line + 2).saturating_sub(height) as u16;
+        frame.render_widget(Paragraph::new(lines).scroll((scroll, 0)), layout[2]);
+    }
+
+    fn is_popup(&self) -> bool {
+        true
+    }
+}
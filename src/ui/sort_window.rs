@@ -0,0 +1,77 @@
+use crossterm::event::KeyCode;
+use log::error;
+use ratatui::{prelude::{Rect, Layout, Direction, Constraint}, widgets::{Block, Borders, Clear, List, ListItem, Paragraph}, style::{Style, Color}};
+
+use crate::{store::SortKey, ui::popup_rect, Frame, LetterEvent, LetterState, Window, WindowCommand, _WindowCommand};
+
+pub struct SortWindow {
+    cursor: usize
+}
+
+impl SortWindow {
+    pub fn new() -> Self {
+        Self { cursor: 0 }
+    }
+}
+
+impl Window for SortWindow {
+    fn handle_event(&mut self, state: &mut LetterState, event: LetterEvent) -> WindowCommand {
+        let LetterEvent::RawKeyInputEvent(key_code) = event else {
+            return None
+        };
+
+        let key_count = SortKey::ALL.len();
+        match key_code {
+            KeyCode::Char('j') | KeyCode::Down => self.cursor = (self.cursor + 1) % key_count,
+            KeyCode::Char('k') | KeyCode::Up => self.cursor = (self.cursor + key_count - 1) % key_count,
+            KeyCode::Enter => {
+                let key = SortKey::ALL[self.cursor];
+                if let Err(err) = state.store.sort_tasks(key) {
+                    error!("couldn't sort tasks by {}: {err}", key.name());
+                    state.message = Some(err.to_string());
+                }
+
+                return Some(_WindowCommand::Quit);
+            },
+            KeyCode::Esc | KeyCode::Char('q') => return Some(_WindowCommand::Quit),
+            _ => {}
+        }
+
+        None
+    }
+
+    fn update(&mut self, _state: &mut LetterState) -> WindowCommand {
+        None
+    }
+
+    fn draw(&self, _state: &LetterState, frame: &mut Frame, rect: Rect) {
+        let rect = popup_rect(32, SortKey::ALL.len() as u16 + 3, rect);
+        let block = Block::default()
+            .title("Sort by")
+            .borders(Borders::ALL);
+
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(1),
+                Constraint::Length(1)
+            ]).split(block.inner(rect));
+
+        let list_items: Vec<ListItem> = SortKey::ALL.iter()
+            .enumerate()
+            .map(|(idx, key)| match idx == self.cursor {
+                true => ListItem::new(format!("> {}", key.name())).style(Style::default().bg(Color::Rgb(20, 0, 20))),
+                false => ListItem::new(format!("  {}", key.name()))
+            })
+            .collect();
+
+        frame.render_widget(Clear, rect);
+        frame.render_widget(block, rect);
+        frame.render_widget(List::new(list_items), layout[0]);
+        frame.render_widget(Paragraph::new("subtasks stay below their parent").style(Style::default().fg(Color::DarkGray)), layout[1]);
+    }
+
+    fn is_popup(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,89 @@
+use chrono::NaiveDateTime;
+use crossterm::event::KeyCode;
+use ratatui::{prelude::Rect, widgets::{Block, Borders, Clear, Paragraph, Wrap}, style::{Style, Color}, text::{Line, Span}};
+
+use crate::{dates, ui::popup_rect, Frame, LetterEvent, LetterState, Window, WindowCommand, _WindowCommand};
+
+// note lines shown below the fields
+const MAX_NOTE_LINES: usize = 6;
+
+pub struct TaskDetailWindow {
+    task_id: i64
+}
+
+impl TaskDetailWindow {
+    pub fn new(task_id: i64) -> Self {
+        Self { task_id }
+    }
+}
+
+fn field_line(name: &str, value: String) -> Line<'static> {
+    Line::from(vec![
+        Span::styled(format!("{name:<10}"), Style::default().fg(Color::DarkGray)),
+        Span::raw(value)
+    ])
+}
+
+impl Window for TaskDetailWindow {
+    fn handle_event(&mut self, _state: &mut LetterState, event: LetterEvent) -> WindowCommand {
+        match event {
+            LetterEvent::RawKeyInputEvent(KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q')) => Some(_WindowCommand::Quit),
+            _ => None
+        }
+    }
+
+    fn update(&mut self, state: &mut LetterState) -> WindowCommand {
+        // the task may be gone after a reload
+        match state.store.tasks.iter().any(|task| task.id == Some(self.task_id)) {
+            true => None,
+            false => Some(_WindowCommand::Quit)
+        }
+    }
+
+    fn draw(&self, state: &LetterState, frame: &mut Frame, rect: Rect) {
+        let store = &state.store;
+        let Some(task) = store.tasks.iter().find(|task| task.id == Some(self.task_id)) else {
+            return
+        };
+
+        let today = dates::today();
+        let timestamp = |timestamp: Option<NaiveDateTime>, missing: &str| {
+            timestamp.map(|timestamp| dates::format_timestamp(timestamp, today)).unwrap_or_else(|| missing.to_string())
+        };
+
+        let mut lines = vec![
+            Line::from(task.text.clone()),
+            Line::from(""),
+            field_line("badge", store.get_badge(task).map(|badge| badge.name.clone()).unwrap_or_else(|| "none".to_string())),
+            field_line("list", store.current_list().map(|list| list.name.clone()).unwrap_or_default()),
+            field_line("due", task.due_date.map(|date| dates::format_date(date, today)).unwrap_or_else(|| "none".to_string())),
+            field_line("start", task.start_date.map(|date| dates::format_date(date, today)).unwrap_or_else(|| "none".to_string())),
+            field_line("created", timestamp(task.created_at, "unknown")),
+            field_line("updated", timestamp(task.updated_at, "unknown")),
+            field_line("completed", timestamp(task.completed_at, "not yet")),
+            field_line("tracked", dates::format_duration(store.tracked_time(self.task_id, dates::now())))
+        ];
+
+        let note = task.note_id
+            .and_then(|note_id| store.get_note_by_id(note_id))
+            .filter(|note| !note.text.is_empty());
+        if let Some(note) = note {
+            let note_times = format!("created {}, updated {}", timestamp(note.created_at, "unknown"), timestamp(note.updated_at, "unknown"));
+            lines.push(field_line("note", note_times));
+            lines.push(Line::from(""));
+            lines.extend(note.text.lines().take(MAX_NOTE_LINES).map(|line| Line::from(line.to_string())));
+        }
+
+        let rect = popup_rect(60, lines.len() as u16 + 2, rect);
+        let block = Block::default()
+            .title("Task")
+            .borders(Borders::ALL);
+
+        frame.render_widget(Clear, rect);
+        frame.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }).block(block), rect);
+    }
+
+    fn is_popup(&self) -> bool {
+        true
+    }
+}
@@ -8,6 +8,8 @@ use crate::{LetterCommand, LetterEvent, CursorDirection, _WindowCommand, WindowC
 pub struct TextArea<S, R> {
     pub lines: Vec<String>,
     cursor: (usize, usize),
+    indents: Vec<u16>,
+    line_styles: Vec<Style>,
     allow_line_breaks: bool,
     callbacks: HashMap<KeyCode, Box<dyn FnMut(&mut Self, &mut S) -> (bool, Option<R>)>>
 }
@@ -19,7 +21,7 @@ impl<S, R> TextArea<S, R> {
             lines = vec!["".to_string()];
         }
 
-        TextArea { lines, cursor: (0, 0), allow_line_breaks: true, callbacks: HashMap::new() }
+        TextArea { lines, cursor: (0, 0), indents: vec![], line_styles: vec![], allow_line_breaks: true, callbacks: HashMap::new() }
     }
 
     pub fn handle_letter_event(&mut self, event: LetterEvent) -> WindowCommand {
@@ -44,6 +46,18 @@ impl<S, R> TextArea<S, R> {
                             DeleteCommand::DeleteChar => self.delete_char_at_cursor(),
                         }
                     },
+                    LetterCommand::Tree(_) | LetterCommand::EditDate(_) | LetterCommand::PickList | LetterCommand::MoveTaskToList
+                        | LetterCommand::Undo | LetterCommand::Redo | LetterCommand::Search
+                        | LetterCommand::ManageBadges
+                        | LetterCommand::OpenTrash
+                        | LetterCommand::ShowDetails
+                        | LetterCommand::SortTasks
+                        | LetterCommand::SelectBadge
+                        | LetterCommand::EditRecurrence
+                        | LetterCommand::StartTimer
+                        | LetterCommand::StopTimer
+                        | LetterCommand::ShowDependencies
+                        | LetterCommand::CycleBadge => {}
                 }
             },
             LetterEvent::RawKeyInputEvent(key_code) => {
@@ -71,6 +85,18 @@ impl<S, R> TextArea<S, R> {
         }
     }
 
+    pub fn set_indents(&mut self, indents: Vec<u16>) {
+        self.indents = indents;
+    }
+
+    pub fn get_indent(&self, y: usize) -> u16 {
+        self.indents.get(y).copied().unwrap_or(0)
+    }
+
+    pub fn set_line_styles(&mut self, line_styles: Vec<Style>) {
+        self.line_styles = line_styles;
+    }
+
     pub fn on_key(&mut self, key_code: KeyCode, callback: Box<dyn FnMut(&mut Self, &mut S) -> (bool, Option<R>)>) {
         self.callbacks.insert(key_code, callback);
     }
@@ -79,7 +105,7 @@ impl<S, R> TextArea<S, R> {
         self.cursor
     }
 
-    pub fn _set_cursor(&mut self, cursor: (usize, usize)) {
+    pub fn set_cursor(&mut self, cursor: (usize, usize)) {
         self.cursor = cursor
     }
 
@@ -351,14 +377,18 @@ impl<S, R> TextArea<S, R> {
             .map(|line| Paragraph::new(line.to_string()))
             .enumerate()
             .map(|(idx, p)| {
+                let style = self.line_styles.get(idx).copied().unwrap_or_default();
                 if idx == y {
-                    (idx, p.style(Style::default().bg(Color::Rgb(100, 100, 100))))
+                    (idx, p.style(style.bg(Color::Rgb(100, 100, 100))))
                 } else {
-                    (idx, p)
+                    (idx, p.style(style))
                 }
             })
-            .for_each(|(index, p)| frame.render_widget(p, Rect::new(rect.x, rect.y + index as u16, rect.width, 1)));
+            .for_each(|(index, p)| {
+                let indent = self.get_indent(index).min(rect.width);
+                frame.render_widget(p, Rect::new(rect.x + indent, rect.y + index as u16, rect.width - indent, 1))
+            });
 
-        frame.set_cursor(rect.x + x as u16, rect.y + y as u16);
+        frame.set_cursor(rect.x + self.get_indent(y) + x as u16, rect.y + y as u16);
     }
 }
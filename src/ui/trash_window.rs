@@ -0,0 +1,147 @@
+use crossterm::event::KeyCode;
+use log::error;
+use ratatui::{prelude::{Rect, Layout, Direction, Constraint}, widgets::{Block, Borders, Clear, List, ListItem, Paragraph}, style::{Style, Color}};
+
+use crate::{dates, store::{TaskStore, TrashedTask}, ui::popup_rect, Frame, LetterEvent, LetterState, Window, WindowCommand, _WindowCommand};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PendingPurge {
+    Selected,
+    All
+}
+
+pub struct TrashWindow {
+    trash: Vec<TrashedTask>,
+    cursor: usize,
+    pending_purge: Option<PendingPurge>,
+    error: Option<String>
+}
+
+impl TrashWindow {
+    pub fn new(store: &TaskStore) -> Self {
+        let mut trash_window = Self {
+            trash: vec![],
+            cursor: 0,
+            pending_purge: None,
+            error: None
+        };
+
+        trash_window.reload(store);
+        trash_window
+    }
+
+    fn reload(&mut self, store: &TaskStore) {
+        self.trash = store.trash().unwrap_or_else(|err| {
+            error!("couldn't load the trash: {err}");
+            vec![]
+        });
+        self.cursor = self.cursor.min(self.trash.len().saturating_sub(1));
+    }
+
+    fn purge(&mut self, store: &mut TaskStore, pending_purge: PendingPurge) {
+        let task_ids: Vec<i64> = match pending_purge {
+            PendingPurge::Selected => self.trash.get(self.cursor).and_then(|trashed| trashed.task.id).into_iter().collect(),
+            PendingPurge::All => self.trash.iter().filter_map(|trashed| trashed.task.id).collect()
+        };
+
+        if let Err(err) = store.purge_tasks(&task_ids) {
+            self.error = Some(err.to_string());
+        }
+        self.reload(store);
+    }
+}
+
+impl Window for TrashWindow {
+    fn handle_event(&mut self, state: &mut LetterState, event: LetterEvent) -> WindowCommand {
+        let LetterEvent::RawKeyInputEvent(key_code) = event else {
+            return None
+        };
+
+        self.error = None;
+        let pending_purge = self.pending_purge.take();
+        let task_count = self.trash.len().max(1);
+        match key_code {
+            KeyCode::Char('j') | KeyCode::Down => self.cursor = (self.cursor + 1) % task_count,
+            KeyCode::Char('k') | KeyCode::Up => self.cursor = (self.cursor + task_count - 1) % task_count,
+            KeyCode::Enter | KeyCode::Char('r') => {
+                let task_id = self.trash.get(self.cursor).and_then(|trashed| trashed.task.id)?;
+
+                match state.store.restore_task(task_id) {
+                    Ok(()) => {
+                        state.focus_task_id = Some(task_id);
+                        return Some(_WindowCommand::Quit);
+                    },
+                    Err(err) => self.error = Some(err.to_string())
+                }
+            },
+            KeyCode::Char('d') if pending_purge == Some(PendingPurge::Selected) => self.purge(&mut state.store, PendingPurge::Selected),
+            KeyCode::Char('d') if !self.trash.is_empty() => self.pending_purge = Some(PendingPurge::Selected),
+            KeyCode::Char('P') if pending_purge == Some(PendingPurge::All) => self.purge(&mut state.store, PendingPurge::All),
+            KeyCode::Char('P') => self.pending_purge = Some(PendingPurge::All),
+            KeyCode::Esc | KeyCode::Char('q') => return Some(_WindowCommand::Quit),
+            _ => {}
+        }
+
+        None
+    }
+
+    fn update(&mut self, _state: &mut LetterState) -> WindowCommand {
+        None
+    }
+
+    fn draw(&self, state: &LetterState, frame: &mut Frame, rect: Rect) {
+        let store = &state.store;
+        let current_list_id = store.current_list().map(|list| list.id);
+        let today = dates::today();
+
+        let rect = popup_rect(64, self.trash.len().clamp(1, 16) as u16 + 3, rect);
+        let block = Block::default()
+            .title("Trash")
+            .borders(Borders::ALL);
+
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(1),
+                Constraint::Length(1)
+            ]).split(block.inner(rect));
+
+        let list_items: Vec<ListItem> = self.trash.iter()
+            .enumerate()
+            .map(|(idx, trashed)| {
+                let deleted_at = dates::format_timestamp(trashed.deleted_at, today);
+                let list_name = store.lists.iter()
+                    .find(|list| list.id == trashed.list_id && Some(list.id) != current_list_id)
+                    .map(|list| format!(" ({})", list.name))
+                    .unwrap_or_default();
+
+                let text = format!("{}{list_name}  {deleted_at}", trashed.task.text);
+                match idx == self.cursor {
+                    true => ListItem::new(format!("> {text}")).style(Style::default().bg(Color::Rgb(20, 0, 20))),
+                    false => ListItem::new(format!("  {text}"))
+                }
+            })
+            .collect();
+
+        frame.render_widget(Clear, rect);
+        frame.render_widget(block, rect);
+        match list_items.is_empty() {
+            true => frame.render_widget(Paragraph::new("  the trash is empty").style(Style::default().fg(Color::DarkGray)), layout[0]),
+            false => frame.render_widget(List::new(list_items), layout[0])
+        }
+
+        let footer_paragraph = match (&self.error, self.pending_purge) {
+            (Some(error), _) => Paragraph::new(error.clone()).style(Style::default().fg(Color::LightRed)),
+            (None, Some(PendingPurge::Selected)) => Paragraph::new("press d again to delete it for good")
+                .style(Style::default().fg(Color::LightYellow)),
+            (None, Some(PendingPurge::All)) => Paragraph::new("press P again to empty the trash for good")
+                .style(Style::default().fg(Color::LightYellow)),
+            (None, None) => Paragraph::new("r restore, dd delete for good, PP empty trash").style(Style::default().fg(Color::DarkGray))
+        };
+        frame.render_widget(footer_paragraph, layout[1]);
+    }
+
+    fn is_popup(&self) -> bool {
+        true
+    }
+}